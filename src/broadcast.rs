@@ -0,0 +1,246 @@
+use crate::cli::NotifyArgs;
+use crate::config::NotificationDefaults;
+use crate::spec::NotificationSpec;
+use clap::{Args, ValueEnum};
+use std::error::Error;
+use std::ffi::{CStr, CString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use tokio::io::AsyncWriteExt;
+use tokio::process::Command;
+use zbus::zvariant::OwnedObjectPath;
+use zbus::{Connection, proxy};
+
+/// Session ID, user ID, user name, seat and object path of a logind session.
+type Session = (String, u32, String, String, OwnedObjectPath);
+
+#[proxy(
+    interface = "org.freedesktop.login1.Manager",
+    default_service = "org.freedesktop.login1",
+    default_path = "/org/freedesktop/login1"
+)]
+trait LoginManager {
+    fn list_sessions(&self) -> zbus::Result<Vec<Session>>;
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SessionSource {
+    /// logind when the system bus has it, otherwise the runtime directory
+    Auto,
+    /// Users with a session in systemd-logind
+    Logind,
+    /// Users with a bus socket in the runtime directory
+    RuntimeDir,
+}
+
+#[derive(Args, Debug)]
+pub struct BroadcastArgs {
+    #[command(flatten)]
+    pub notify: NotifyArgs,
+
+    /// Where to find logged-in users
+    #[arg(long, value_enum, default_value_t = SessionSource::Auto)]
+    pub source: SessionSource,
+
+    /// Directory holding each user's `UID/bus` socket
+    #[arg(long, default_value = "/run/user")]
+    pub runtime_dir: PathBuf,
+}
+
+/// A user account from the password database.
+struct User {
+    name: String,
+    home: PathBuf,
+    gid: u32,
+}
+
+fn lookup_user(uid: u32) -> Option<User> {
+    let mut buffer = vec![0; 16384];
+    // SAFETY: passwd is plain data that getpwuid_r fills in.
+    let mut passwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut result = std::ptr::null_mut();
+    // SAFETY: the buffer outlives the call and its length is passed along.
+    let status = unsafe {
+        libc::getpwuid_r(
+            uid,
+            &mut passwd,
+            buffer.as_mut_ptr(),
+            buffer.len(),
+            &mut result,
+        )
+    };
+    if status != 0 || result.is_null() {
+        return None;
+    }
+    // SAFETY: on success the strings point into the buffer and are NUL terminated.
+    let (name, home) = unsafe {
+        (
+            CStr::from_ptr(passwd.pw_name),
+            CStr::from_ptr(passwd.pw_dir),
+        )
+    };
+    Some(User {
+        name: name.to_string_lossy().into_owned(),
+        home: PathBuf::from(home.to_string_lossy().into_owned()),
+        gid: passwd.pw_gid,
+    })
+}
+
+/// The groups `name` belongs to, including its primary group `gid`.
+fn group_list(name: &CStr, gid: u32) -> Result<Vec<libc::gid_t>, Box<dyn Error>> {
+    let mut groups: Vec<libc::gid_t> = vec![0; 32];
+    loop {
+        let mut count = libc::c_int::try_from(groups.len())?;
+        // SAFETY: the list holds `count` entries; on failure getgrouplist stores the number
+        // it needs there instead.
+        let status =
+            unsafe { libc::getgrouplist(name.as_ptr(), gid, groups.as_mut_ptr(), &mut count) };
+        let count = usize::try_from(count)?;
+        if status >= 0 {
+            groups.truncate(count);
+            return Ok(groups);
+        }
+        if count <= groups.len() {
+            return Err(format!("failed to list the groups of {}", name.to_string_lossy()).into());
+        }
+        groups.resize(count, 0);
+    }
+}
+
+/// User IDs with a `UID/bus` socket in the runtime directory.
+fn users_from_runtime_dir(runtime_dir: &Path) -> Result<Vec<u32>, Box<dyn Error>> {
+    let entries = fs::read_dir(runtime_dir)
+        .map_err(|e| format!("failed to read {}: {e}", runtime_dir.display()))?;
+    Ok(entries
+        .flatten()
+        .filter_map(|entry| entry.file_name().to_str()?.parse().ok())
+        .filter(|uid: &u32| runtime_dir.join(uid.to_string()).join("bus").exists())
+        .collect())
+}
+
+async fn users_from_logind() -> zbus::Result<Vec<u32>> {
+    let connection = Connection::system().await?;
+    let sessions = LoginManagerProxy::new(&connection)
+        .await?
+        .list_sessions()
+        .await?;
+    Ok(sessions.into_iter().map(|(_, uid, ..)| uid).collect())
+}
+
+/// Sends the notification on one user's bus by running `alertify send` as that user, and
+/// returns how it went.
+async fn send_as(uid: u32, bus: &Path, spec: &str) -> Result<String, Box<dyn Error>> {
+    let mut command = Command::new(std::env::current_exe()?);
+    command
+        .arg("--bus-address")
+        .arg(format!("unix:path={}", bus.display()))
+        .args(["send", "--from", "-", "--format", "json"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped());
+
+    // SAFETY: getuid has no preconditions and can't fail.
+    if uid != unsafe { libc::getuid() } {
+        let user = lookup_user(uid).ok_or_else(|| format!("no user with ID {uid}"))?;
+        // The user's own environment, so their config and do-not-disturb apply.
+        command
+            .env_clear()
+            .env("HOME", &user.home)
+            .env("USER", &user.name)
+            .env("LOGNAME", &user.name)
+            .env("PATH", "/usr/local/bin:/usr/bin:/bin")
+            .env("XDG_RUNTIME_DIR", bus.parent().unwrap_or(bus));
+        let gid = user.gid;
+        let groups = group_list(&CString::new(user.name)?, gid)?;
+        // Groups have to be set while still root, so this replaces `CommandExt::uid`, whose
+        // change happens before `pre_exec` runs.
+        // SAFETY: setgroups, setgid and setuid are async-signal-safe, and the group list was
+        // resolved before forking, as looking it up may allocate or take locks.
+        unsafe {
+            command.pre_exec(move || {
+                if libc::setgroups(groups.len(), groups.as_ptr()) != 0
+                    || libc::setgid(gid) != 0
+                    || libc::setuid(uid) != 0
+                {
+                    return Err(std::io::Error::last_os_error());
+                }
+                Ok(())
+            });
+        }
+    }
+
+    let mut child = command.spawn()?;
+    let mut stdin = child.stdin.take().ok_or("child stdin was not captured")?;
+    stdin.write_all(spec.as_bytes()).await?;
+    drop(stdin);
+
+    let output = child.wait_with_output().await?;
+    // `send` reports each notification as `[0] sent (id 5)`.
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let report = stdout
+        .lines()
+        .next()
+        .map(|line| line.trim_start_matches("[0] ").to_string());
+    if output.status.success() {
+        return Ok(report.unwrap_or_else(|| String::from("sent")));
+    }
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    let reason = report
+        .and_then(|line| line.strip_prefix("failed: ").map(str::to_string))
+        .or_else(|| stderr.lines().last().map(str::to_string))
+        .unwrap_or_else(|| format!("exited with {}", output.status));
+    Err(reason.into())
+}
+
+/// Sends the same notification to every logged-in user's session bus.
+pub async fn handle_broadcast(
+    args: BroadcastArgs,
+    defaults: &NotificationDefaults,
+) -> Result<(), Box<dyn Error>> {
+    let mut notification = args.notify.to_notification(defaults)?;
+    notification.actions.clear();
+    let spec = serde_json::to_string(&NotificationSpec::from(&notification))?;
+
+    let mut uids = match args.source {
+        SessionSource::RuntimeDir => users_from_runtime_dir(&args.runtime_dir)?,
+        SessionSource::Logind => users_from_logind()
+            .await
+            .map_err(|e| format!("failed to list sessions from logind: {e}"))?,
+        SessionSource::Auto => match users_from_logind().await {
+            Ok(uids) => uids,
+            Err(_) => users_from_runtime_dir(&args.runtime_dir)?,
+        },
+    };
+    uids.sort_unstable();
+    uids.dedup();
+    if uids.is_empty() {
+        return Err("no logged-in users found".into());
+    }
+
+    let total = uids.len();
+    let mut failed = 0;
+    for uid in uids {
+        let bus = args.runtime_dir.join(uid.to_string()).join("bus");
+        let result = if bus.exists() {
+            send_as(uid, &bus, &spec).await
+        } else {
+            Err(format!("no session bus at {}", bus.display()).into())
+        };
+        let name = lookup_user(uid)
+            .map(|user| user.name)
+            .unwrap_or_else(|| uid.to_string());
+        match result {
+            Ok(report) => println!("{name} ({uid}): {report}"),
+            Err(e) => {
+                failed += 1;
+                println!("{name} ({uid}): failed: {e}");
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{failed} of {total} sessions failed").into());
+    }
+    Ok(())
+}
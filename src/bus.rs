@@ -0,0 +1,255 @@
+use std::fs;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+use zbus::{Connection, connection};
+
+const ADDRESS_VAR: &str = "DBUS_SESSION_BUS_ADDRESS";
+
+/// The session bus address, once given by `--bus-address` or discovered.
+static ADDRESS: OnceLock<Option<String>> = OnceLock::new();
+
+/// Uses `address` for the session bus instead of discovering it. Call before connecting.
+pub fn set_address(address: String) {
+    let _ = ADDRESS.set(Some(address));
+}
+
+/// Variables set in the environment of processes that belong to a graphical or login
+/// session, as opposed to a stray daemon that happened to inherit an old address.
+const SESSION_VARS: [&str; 3] = ["XDG_SESSION_ID", "DISPLAY", "WAYLAND_DISPLAY"];
+
+/// The non-empty value of `name` in a process environment block.
+fn environ_var(environ: &[u8], name: &str) -> Option<String> {
+    environ.split(|byte| *byte == 0).find_map(|entry| {
+        let value = entry.strip_prefix(name.as_bytes())?.strip_prefix(b"=")?;
+        let value = String::from_utf8(value.to_vec()).ok()?;
+        (!value.is_empty()).then_some(value)
+    })
+}
+
+/// The `DBUS_SESSION_BUS_ADDRESS` value in a process environment block.
+fn address_in_environ(environ: &[u8]) -> Option<String> {
+    environ_var(environ, ADDRESS_VAR)
+}
+
+fn under_root(root: &Path, path: &str) -> PathBuf {
+    root.join(path.trim_start_matches('/'))
+}
+
+/// Whether an abstract socket named `name` is listening, going by `/proc/net/unix`, where
+/// abstract names are shown with a leading `@`.
+fn abstract_socket_exists(root: &Path, name: &str) -> bool {
+    let Ok(sockets) = fs::read_to_string(under_root(root, "/proc/net/unix")) else {
+        // Without the list there is nothing to go by.
+        return true;
+    };
+    sockets
+        .lines()
+        .skip(1)
+        .filter_map(|line| line.split_whitespace().nth(7)?.strip_prefix('@'))
+        .any(|listed| listed == name)
+}
+
+/// Whether any part of `address` points at a Unix socket that still exists, checking
+/// paths on disk and abstract sockets in `/proc/net/unix`. Other transports can't be
+/// checked without connecting, so they are assumed to be fine.
+fn looks_alive(root: &Path, address: &str) -> bool {
+    address
+        .split(';')
+        .filter(|part| !part.is_empty())
+        .any(|part| {
+            let Some(params) = part.strip_prefix("unix:") else {
+                return true;
+            };
+            let socket = params
+                .split(',')
+                .filter_map(|param| param.split_once('='))
+                .find(|(key, _)| matches!(*key, "path" | "abstract"));
+            match socket {
+                Some(("path", path)) => under_root(root, path).exists(),
+                Some((_, name)) => abstract_socket_exists(root, name),
+                // `dir=`, `tmpdir=` and `runtime=` are only for listening.
+                None => true,
+            }
+        })
+}
+
+/// Finds the address in the environment of another of our processes, such as the
+/// desktop session that cron or a system service doesn't inherit from.
+///
+/// Processes that belong to a session are preferred, then the lowest PID, since
+/// long-running session processes have the lowest PIDs and the most reliable address.
+fn address_from_processes(root: &Path, uid: u32) -> Option<String> {
+    let proc = under_root(root, "/proc");
+    let mut candidates: Vec<(bool, u32, String)> = fs::read_dir(&proc)
+        .ok()?
+        .flatten()
+        .filter_map(|entry| {
+            let pid: u32 = entry.file_name().to_str()?.parse().ok()?;
+            if entry.metadata().ok()?.uid() != uid {
+                return None;
+            }
+            let environ = fs::read(entry.path().join("environ")).ok()?;
+            let address = address_in_environ(&environ)?;
+            let in_session = SESSION_VARS
+                .iter()
+                .any(|name| environ_var(&environ, name).is_some());
+            Some((!in_session, pid, address))
+        })
+        .collect();
+    candidates.sort_unstable();
+
+    candidates
+        .into_iter()
+        .map(|(_, _, address)| address)
+        .find(|address| looks_alive(root, address))
+}
+
+/// Looks for the session bus of `uid` under `root`: the systemd user bus socket in
+/// `/run/user/UID`, then the environment of its processes.
+fn discover_under(root: &Path, uid: u32) -> Option<String> {
+    let socket = format!("/run/user/{uid}/bus");
+    if under_root(root, &socket).exists() {
+        return Some(format!("unix:path={socket}"));
+    }
+    address_from_processes(root, uid)
+}
+
+/// Looks for the session bus outside the environment.
+fn discover() -> Option<String> {
+    if std::env::var_os(ADDRESS_VAR).is_some_and(|address| !address.is_empty()) {
+        return None;
+    }
+    // SAFETY: getuid has no preconditions and can't fail.
+    let uid = unsafe { libc::getuid() };
+    discover_under(Path::new("/"), uid)
+}
+
+/// The address of the session bus when it isn't simply `DBUS_SESSION_BUS_ADDRESS`.
+pub fn session_address() -> Option<&'static str> {
+    ADDRESS.get_or_init(discover).as_deref()
+}
+
+/// A connection builder for the session bus, found even when the environment lacks it.
+pub fn session_builder() -> zbus::Result<connection::Builder<'static>> {
+    match session_address() {
+        Some(address) => connection::Builder::address(address),
+        None => connection::Builder::session(),
+    }
+}
+
+/// Connects to the session bus, found even when the environment lacks it.
+pub async fn session() -> zbus::Result<Connection> {
+    match session_address() {
+        Some(address) => connection::Builder::address(address)?.build().await,
+        None => Connection::session().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::FakeRoot;
+
+    /// Writes the environment block of process `pid`.
+    fn process(root: &FakeRoot, pid: u32, vars: &[&str]) {
+        let environ: Vec<u8> = vars
+            .iter()
+            .flat_map(|var| [var.as_bytes(), b"\0"].concat())
+            .collect();
+        root.write(&format!("/proc/{pid}/environ"), environ);
+    }
+
+    /// Creates a file standing in for a socket and returns its address.
+    fn socket(root: &FakeRoot, path: &str) -> String {
+        root.write(path, "");
+        format!("unix:path={path}")
+    }
+
+    fn uid() -> u32 {
+        // SAFETY: getuid has no preconditions and can't fail.
+        unsafe { libc::getuid() }
+    }
+
+    #[test]
+    fn reads_the_address_from_an_environment_block() {
+        let environ = b"HOME=/home/me\0DBUS_SESSION_BUS_ADDRESS_OLD=x\0DBUS_SESSION_BUS_ADDRESS=unix:path=/run/bus\0";
+        assert_eq!(
+            address_in_environ(environ).as_deref(),
+            Some("unix:path=/run/bus")
+        );
+        assert_eq!(address_in_environ(b"DBUS_SESSION_BUS_ADDRESS=\0"), None);
+        assert_eq!(address_in_environ(b"HOME=/home/me"), None);
+    }
+
+    #[test]
+    fn checks_path_and_abstract_sockets() {
+        let root = FakeRoot::new("alive");
+        let live = socket(&root, "/tmp/live");
+        let gone = "unix:path=/tmp/gone";
+        assert!(looks_alive(root.path(), &live));
+        assert!(!looks_alive(root.path(), gone));
+        assert!(looks_alive(root.path(), &format!("{gone};{live}")));
+        assert!(looks_alive(root.path(), "tcp:host=localhost,port=4000"));
+
+        root.write(
+            "/proc/net/unix",
+            b"Num       RefCount Protocol Flags    Type St Inode Path\n\
+              0000000000000000: 00000002 00000000 00010000 0001 01 21436 @/tmp/dbus-live\n\
+              0000000000000000: 00000003 00000000 00000000 0001 03 21437\n",
+        );
+        assert!(looks_alive(
+            root.path(),
+            "unix:abstract=/tmp/dbus-live,guid=1234"
+        ));
+        assert!(!looks_alive(root.path(), "unix:abstract=/tmp/dbus-gone"));
+    }
+
+    #[test]
+    fn prefers_the_user_bus_socket() {
+        let root = FakeRoot::new("run-user");
+        let session = socket(&root, "/tmp/session");
+        process(
+            &root,
+            10,
+            &[&format!("DBUS_SESSION_BUS_ADDRESS={session}"), "DISPLAY=:0"],
+        );
+        let user_bus = socket(&root, &format!("/run/user/{}/bus", uid()));
+        assert_eq!(discover_under(root.path(), uid()), Some(user_bus));
+    }
+
+    #[test]
+    fn falls_back_to_a_session_process_with_a_live_bus() {
+        let root = FakeRoot::new("proc");
+        assert_eq!(discover_under(root.path(), uid()), None);
+
+        let stray = socket(&root, "/tmp/stray");
+        let session = socket(&root, "/tmp/session");
+        let stale = "unix:path=/tmp/stale";
+        process(&root, 5, &[&format!("DBUS_SESSION_BUS_ADDRESS={stray}")]);
+        process(
+            &root,
+            7,
+            &[
+                &format!("DBUS_SESSION_BUS_ADDRESS={stale}"),
+                "XDG_SESSION_ID=1",
+            ],
+        );
+        process(
+            &root,
+            9,
+            &[
+                &format!("DBUS_SESSION_BUS_ADDRESS={session}"),
+                "WAYLAND_DISPLAY=wayland-0",
+            ],
+        );
+        process(&root, 3, &["DISPLAY=:0"]);
+        assert_eq!(discover_under(root.path(), uid()), Some(session));
+
+        // Without a session process, the lowest PID wins.
+        fs::remove_dir_all(root.path().join("proc/9")).unwrap();
+        assert_eq!(discover_under(root.path(), uid()), Some(stray));
+        // Someone else's processes are never used.
+        assert_eq!(discover_under(root.path(), uid() + 1), None);
+    }
+}
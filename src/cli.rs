@@ -1,46 +1,118 @@
+use crate::broadcast::BroadcastArgs;
+use crate::clock::parse_at;
+use crate::compat::NotifySendArgs;
+use crate::config::{Config, NotificationDefaults};
+use crate::daemon::DaemonArgs;
+use crate::dnd::DndAction;
+use crate::duration::{parse_duration, parse_timeout};
+use crate::history::HistoryArgs;
 use crate::icons::utils::IconSet;
+use crate::jobs::JobsCommand;
+use crate::notification::{DEFAULT_BODY, DEFAULT_TITLE, Notification, Urgency};
+use crate::pomodoro::parse_pomodoro_length;
+use crate::replay::ReplayArgs;
+use crate::run::RunArgs;
+use crate::serve::ServeArgs;
+use crate::spec::SpecFormat;
+use crate::sysmon::MonitorSystemArgs;
+use crate::tail::TailArgs;
+use crate::template::{load_template, parse_var};
+use crate::throttle::{RateLimit, Throttle, parse_rate_limit};
+use crate::waitpid::WaitPidArgs;
+use crate::watch::WatchArgs;
+use chrono::{DateTime, Local};
+use clap::Args;
 use clap::Parser;
 use clap::Subcommand;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
 
 #[derive(Parser, Debug)]
 #[command(author, version, about, long_about = None)]
 pub struct Cli {
+    /// Config file to use instead of `$XDG_CONFIG_HOME/alertify/config.toml`
+    #[arg(long, global = true, env = "ALERTIFY_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Named `[profile.NAME]` section of the config file to apply
+    #[arg(short = 'P', long, global = true, env = "ALERTIFY_PROFILE")]
+    pub profile: Option<String>,
+
+    /// Wait up to TIMEOUT for a notification server to appear before sending, e.g. 30s
+    ///
+    /// Only commands that send right away wait: notify, send, replay, flush, compat,
+    /// `dnd off` and `defaults --pomodoro`. Servers, monitors and commands that only read
+    /// state never do, so setting it in the environment doesn't hold them up.
+    #[arg(
+        long,
+        global = true,
+        value_name = "TIMEOUT",
+        value_parser = parse_duration,
+        env = "ALERTIFY_WAIT_FOR_SERVER"
+    )]
+    pub wait_for_server: Option<Duration>,
+
+    /// Session bus address to use instead of finding the logged-in user's bus
+    #[arg(long, global = true, value_name = "ADDRESS")]
+    pub bus_address: Option<String>,
+
     #[command(subcommand)]
     pub command: Commands,
 }
 
 #[derive(Debug, Subcommand)]
 pub enum Commands {
-    Notify {
-        /// Application name for the notification
-        #[arg(short, long, default_value_t = String::from("my_app"))]
-        app_name: String,
-
-        /// Replaces ID of the notification to replace
-        #[arg(short = 'r', long, default_value_t = 0)]
-        replaces_id: u32,
-
-        /// Notification title or summary
-        #[arg(short, long, default_value_t = String::from("A summary"))]
-        title: String,
-
-        /// Notification body text
-        #[arg(short, long, default_value_t = String::from("Some body"))]
-        body: String,
-
-        /// Icon name
-        #[arg(short, long, default_value_t = String::from("dialog-information"))]
-        icon: String,
-
-        /// Notification timeout in milliseconds
-        #[arg(short = 's', long, default_value_t = 5000)]
-        timeout: i32,
+    Notify(NotifyArgs),
+
+    /// Send a notification after a delay, e.g. `in 20m -t Tea`
+    In {
+        #[arg(value_parser = parse_duration)]
+        delay: Duration,
+
+        #[command(flatten)]
+        notify: NotifyArgs,
+    },
+
+    /// Send a notification at a time of day or date, e.g. `at 14:30 -t Stand-up`
+    At {
+        #[arg(value_parser = parse_at)]
+        time: DateTime<Local>,
+
+        #[command(flatten)]
+        notify: NotifyArgs,
+    },
+
+    /// Manage reminders scheduled with `in` and `at`
+    Jobs {
+        #[command(subcommand)]
+        command: JobsCommand,
+    },
+
+    Send {
+        /// Read notifications from a JSON or TOML file, or `-` for stdin
+        #[arg(short, long)]
+        from: String,
+
+        /// Document format, detected from the file extension or content if omitted
+        #[arg(long, value_enum)]
+        format: Option<SpecFormat>,
     },
 
     Defaults {
         /// Pomodoro mode
         #[arg(short, long, default_value_t = false)]
         pomodoro: bool,
+
+        /// Length of the pomodoro work block, e.g. 50m [default: 25m]
+        #[arg(
+            long,
+            requires = "pomodoro",
+            value_parser = parse_pomodoro_length,
+            default_value = "default",
+            hide_default_value = true
+        )]
+        length: Duration,
     },
 
     ListIcons {
@@ -48,4 +120,193 @@ pub enum Commands {
         #[arg(short, long, value_enum, default_value_t = IconSet::All)]
         set: IconSet,
     },
+
+    /// Turn do-not-disturb on or off, or show its status
+    Dnd {
+        #[arg(value_enum, default_value_t = DndAction::Status)]
+        action: DndAction,
+    },
+
+    /// Show notifications sent by alertify
+    ///
+    /// The invoked action and close reason are only known for notifications sent by
+    /// `compat --wait`, `daemon` and the timers of `service`, which stay connected until
+    /// the notification is closed. Every other command, including `notify` with actions,
+    /// `send`, `run` and reminders, exits right after sending, so those are left blank.
+    History(HistoryArgs),
+
+    /// Re-send notifications from a JSONL recording
+    Replay(ReplayArgs),
+
+    /// Run a command and notify when it finishes, e.g. `run -- make test`
+    Run(RunArgs),
+
+    /// Notify when already running processes exit
+    WaitPid(WaitPidArgs),
+
+    /// Notify when files are created, modified or deleted
+    Watch(WatchArgs),
+
+    /// Follow a log file and notify about lines matching rules
+    Tail(TailArgs),
+
+    /// Notify when battery, disk, memory or temperature cross a threshold
+    MonitorSystem(MonitorSystemArgs),
+
+    /// Act as the notification server, printing notifications to the terminal or a log
+    Serve(ServeArgs),
+
+    /// Print every notification sent on the session bus as JSON, with its ID and fate
+    Monitor,
+
+    /// Send a notification to every logged-in user's session, for use as root
+    Broadcast(BroadcastArgs),
+
+    /// Deliver notifications spooled while no notification server was reachable
+    Flush,
+
+    /// Keep a bus connection open and take JSON requests on a Unix socket
+    Daemon(DaemonArgs),
+
+    /// Offer reminders, do-not-disturb, timers and history as a D-Bus service
+    Service,
+
+    /// Behave like libnotify's `notify-send`
+    #[command(disable_help_flag = true)]
+    Compat(NotifySendArgs),
+}
+
+impl Commands {
+    /// Whether the command reads the config file, so one that doesn't keeps working
+    /// while the config is broken.
+    pub fn uses_config(&self) -> bool {
+        !matches!(
+            self,
+            Commands::ListIcons { .. }
+                | Commands::Dnd { .. }
+                | Commands::History(_)
+                | Commands::Replay(_)
+                | Commands::Serve(_)
+                | Commands::Monitor
+                | Commands::Compat(_)
+        )
+    }
+
+    /// Whether the command sends a notification as soon as it starts, which is when
+    /// `--wait-for-server` applies.
+    pub fn sends_right_away(&self) -> bool {
+        match self {
+            Commands::Notify(_)
+            | Commands::Send { .. }
+            | Commands::Replay(_)
+            | Commands::Flush
+            | Commands::Compat(_) => true,
+            Commands::Dnd { action } => matches!(action, DndAction::Off),
+            Commands::Defaults { pomodoro, .. } => *pomodoro,
+            _ => false,
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct NotifyArgs {
+    /// Application name for the notification [default: my_app]
+    #[arg(short, long)]
+    pub app_name: Option<String>,
+
+    /// Replaces ID of the notification to replace [default: 0]
+    #[arg(short = 'r', long)]
+    pub replaces_id: Option<u32>,
+
+    /// Notification title or summary [default: "A summary"]
+    #[arg(short, long)]
+    pub title: Option<String>,
+
+    /// Notification body text [default: "Some body"]
+    #[arg(short, long)]
+    pub body: Option<String>,
+
+    /// Icon name [default: dialog-information]
+    #[arg(short, long)]
+    pub icon: Option<String>,
+
+    /// How long the notification stays up, e.g. 10s, `never` or `default` [default: 5s]
+    #[arg(short = 's', long, value_parser = parse_timeout, allow_negative_numbers = true)]
+    pub timeout: Option<i32>,
+
+    /// Notification urgency [default: normal]
+    #[arg(short, long, value_enum)]
+    pub urgency: Option<Urgency>,
+
+    /// Render a named template from the `templates` config directory
+    #[arg(short = 'T', long)]
+    pub template: Option<String>,
+
+    /// Template variable, as key=value
+    #[arg(long = "var", value_parser = parse_var, requires = "template")]
+    pub vars: Vec<(String, String)>,
+
+    /// Drop the notification if an identical one was sent within this window, e.g. 5m
+    #[arg(long, value_parser = parse_duration)]
+    pub dedupe_window: Option<Duration>,
+
+    /// Key identifying duplicates instead of the app name, title and body
+    #[arg(long, requires = "dedupe_window")]
+    pub dedupe_key: Option<String>,
+
+    /// Limit this app to MAX notifications per DURATION, e.g. 5/1m, collapsing the rest
+    #[arg(long, value_parser = parse_rate_limit)]
+    pub rate_limit: Option<RateLimit>,
+}
+
+impl NotifyArgs {
+    /// Builds the notification, letting explicit flags override the template and
+    /// the template override `defaults`.
+    pub fn to_notification(
+        &self,
+        defaults: &NotificationDefaults,
+    ) -> Result<Notification, Box<dyn Error>> {
+        let mut notification = match &self.template {
+            Some(name) => load_template(name, &self.vars)?.into_notification(defaults),
+            None => {
+                defaults.to_notification(String::from(DEFAULT_TITLE), String::from(DEFAULT_BODY))
+            }
+        };
+
+        if let Some(app_name) = &self.app_name {
+            notification.app_name = app_name.clone();
+        }
+        if let Some(replaces_id) = self.replaces_id {
+            notification.replaces_id = replaces_id;
+        }
+        if let Some(title) = &self.title {
+            notification.title = title.clone();
+        }
+        if let Some(body) = &self.body {
+            notification.body = body.clone();
+        }
+        if let Some(icon) = &self.icon {
+            notification.icon = icon.clone();
+        }
+        if let Some(timeout) = self.timeout {
+            notification.timeout = timeout;
+        }
+        if let Some(urgency) = self.urgency {
+            notification.urgency = urgency;
+        }
+
+        Ok(notification)
+    }
+
+    /// Combines the dedupe flags with the rate limit for `app_name`, preferring `--rate-limit`
+    /// over the config file.
+    pub fn throttle(&self, config: &Config, app_name: &str) -> Throttle {
+        Throttle {
+            dedupe_window: self.dedupe_window,
+            dedupe_key: self.dedupe_key.clone(),
+            rate_limit: self
+                .rate_limit
+                .or_else(|| config.rate_limit.get(app_name).copied()),
+        }
+    }
 }
@@ -0,0 +1,108 @@
+use chrono::{
+    DateTime, Datelike, Duration as ChronoDuration, Local, NaiveDate, NaiveDateTime, NaiveTime,
+    TimeZone, Weekday,
+};
+use serde::{Deserialize, Deserializer};
+
+/// Parses a wall-clock time written as `HH:MM` or `HH:MM:SS`.
+pub fn parse_time_of_day(s: &str) -> Result<NaiveTime, String> {
+    NaiveTime::parse_from_str(s.trim(), "%H:%M")
+        .or_else(|_| NaiveTime::parse_from_str(s.trim(), "%H:%M:%S"))
+        .map_err(|_| format!("invalid time of day `{s}`, expected HH:MM"))
+}
+
+/// Deserializes a time of day written as a string, e.g. `start = "22:00"`.
+pub fn deserialize_time_of_day<'de, D>(deserializer: D) -> Result<NaiveTime, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    parse_time_of_day(&text).map_err(serde::de::Error::custom)
+}
+
+/// A recurring period of the day, such as quiet hours or working hours.
+///
+/// ```toml
+/// start = "22:00"
+/// end = "07:00"
+/// days = ["mon", "tue", "wed", "thu", "fri"]
+/// ```
+///
+/// `days` names the day a period starts on and defaults to every day.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TimeWindow {
+    #[serde(deserialize_with = "deserialize_time_of_day")]
+    pub start: NaiveTime,
+    #[serde(deserialize_with = "deserialize_time_of_day")]
+    pub end: NaiveTime,
+    #[serde(default)]
+    pub days: Option<Vec<Weekday>>,
+}
+
+impl TimeWindow {
+    fn starts_on(&self, day: Weekday) -> bool {
+        self.days.as_ref().is_none_or(|days| days.contains(&day))
+    }
+
+    pub fn contains(&self, now: DateTime<Local>) -> bool {
+        let time = now.time();
+        if self.start <= self.end {
+            self.starts_on(now.weekday()) && time >= self.start && time < self.end
+        } else {
+            // Spans midnight: the early part belongs to the period that started yesterday.
+            (time >= self.start && self.starts_on(now.weekday()))
+                || (time < self.end && self.starts_on((now - ChronoDuration::days(1)).weekday()))
+        }
+    }
+}
+
+/// Parses an RFC 3339 timestamp, or a local `YYYY-MM-DD[ HH:MM[:SS]]`.
+pub fn parse_time(s: &str) -> Result<DateTime<Local>, String> {
+    if let Ok(time) = DateTime::parse_from_rfc3339(s) {
+        return Ok(time.with_timezone(&Local));
+    }
+
+    let naive = [
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M:%S",
+        "%Y-%m-%dT%H:%M",
+    ]
+    .iter()
+    .find_map(|format| NaiveDateTime::parse_from_str(s, format).ok())
+    .or_else(|| {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d")
+            .ok()
+            .and_then(|date| date.and_hms_opt(0, 0, 0))
+    })
+    .ok_or_else(|| {
+        format!("invalid time `{s}`, expected RFC 3339, YYYY-MM-DD or YYYY-MM-DD HH:MM")
+    })?;
+
+    Local
+        .from_local_datetime(&naive)
+        .earliest()
+        .ok_or_else(|| format!("`{s}` does not exist in the local time zone"))
+}
+
+/// Returns the next time the wall clock shows `time`, today or tomorrow.
+pub fn next_occurrence(time: NaiveTime, now: DateTime<Local>) -> Option<DateTime<Local>> {
+    let today = now.date_naive();
+    [today, today.succ_opt()?]
+        .into_iter()
+        .filter_map(|date| Local.from_local_datetime(&date.and_time(time)).earliest())
+        .find(|candidate| *candidate > now)
+}
+
+/// Parses the target of `alertify at`: a time of day, which means its next occurrence,
+/// or anything accepted by [`parse_time`].
+pub fn parse_at(s: &str) -> Result<DateTime<Local>, String> {
+    match parse_time_of_day(s) {
+        Ok(time) => next_occurrence(time, Local::now())
+            .ok_or_else(|| format!("`{s}` does not exist in the local time zone")),
+        Err(_) => parse_time(s).map_err(|_| {
+            format!("invalid time `{s}`, expected HH:MM, YYYY-MM-DD HH:MM or RFC 3339")
+        }),
+    }
+}
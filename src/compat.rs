@@ -0,0 +1,213 @@
+use crate::clock::TimeWindow;
+use crate::deliver::Sender;
+use crate::duration::parse_timeout;
+use crate::history::{record_action, record_closed};
+use crate::notification::{HintValue, Notification, Urgency};
+use crate::throttle::{Outcome, Throttle};
+use clap::{ArgAction, Args, Parser};
+use futures_lite::StreamExt;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::Path;
+
+/// Standalone parser used when the binary is invoked as `notify-send`.
+#[derive(Parser, Debug)]
+#[command(name = "notify-send", disable_help_flag = true)]
+pub struct NotifySend {
+    #[command(flatten)]
+    pub args: NotifySendArgs,
+}
+
+/// The `notify-send` command line, as shipped by libnotify.
+#[derive(Args, Debug)]
+pub struct NotifySendArgs {
+    /// Specifies the urgency level
+    #[arg(short, long, value_enum, default_value_t = Urgency::Normal)]
+    pub urgency: Urgency,
+
+    /// Specifies the timeout in milliseconds at which to expire the notification, or a
+    /// duration such as 5s, `never` or `default`
+    #[arg(
+        short = 't',
+        long,
+        value_parser = parse_timeout,
+        default_value = "default",
+        allow_negative_numbers = true
+    )]
+    pub expire_time: i32,
+
+    /// Specifies the app name for the notification
+    #[arg(short, long, default_value_t = String::from("notify-send"))]
+    pub app_name: String,
+
+    /// Specifies an icon filename or stock icon to display
+    #[arg(short, long, default_value_t = String::new())]
+    pub icon: String,
+
+    /// Specifies the notification category
+    #[arg(short, long)]
+    pub category: Option<String>,
+
+    /// Specifies basic extra data to pass, as TYPE:NAME:VALUE
+    #[arg(short = 'h', long = "hint", value_parser = parse_hint)]
+    pub hints: Vec<(String, HintValue)>,
+
+    /// Specifies the actions to display to the user, as [NAME=]Text
+    #[arg(short = 'A', long = "action")]
+    pub actions: Vec<String>,
+
+    /// Wait for the notification to be closed before exiting
+    #[arg(short, long, default_value_t = false)]
+    pub wait: bool,
+
+    /// Print the notification ID
+    #[arg(short, long, default_value_t = false)]
+    pub print_id: bool,
+
+    /// The ID of the notification to replace
+    #[arg(short, long, default_value_t = 0)]
+    pub replace_id: u32,
+
+    /// Create a transient notification
+    #[arg(short = 'e', long, default_value_t = false)]
+    pub transient: bool,
+
+    /// Print help
+    #[arg(long, action = ArgAction::Help)]
+    pub help: Option<bool>,
+
+    /// Notification summary
+    pub summary: String,
+
+    /// Notification body text
+    pub body: Option<String>,
+}
+
+impl NotifySendArgs {
+    pub fn to_notification(&self) -> Notification {
+        let mut notification = Notification::new(
+            self.app_name.clone(),
+            self.replace_id,
+            self.summary.clone(),
+            self.body.clone().unwrap_or_default(),
+            self.icon.clone(),
+            self.expire_time,
+        );
+        notification.urgency = self.urgency;
+        notification.category = self.category.clone();
+        notification.transient = self.transient;
+        notification.hints = self.hints.iter().cloned().collect::<HashMap<_, _>>();
+        notification.actions = self
+            .actions
+            .iter()
+            .enumerate()
+            .map(|(index, action)| match action.split_once('=') {
+                Some((name, label)) => (name.to_string(), label.to_string()),
+                None => (index.to_string(), action.clone()),
+            })
+            .collect();
+        notification
+    }
+}
+
+/// Returns true when argv[0] names `notify-send`.
+pub fn invoked_as_notify_send() -> bool {
+    std::env::args_os()
+        .next()
+        .and_then(|arg0| {
+            Path::new(&arg0)
+                .file_name()
+                .map(|name| name == "notify-send")
+        })
+        .unwrap_or(false)
+}
+
+fn parse_hint(s: &str) -> Result<(String, HintValue), String> {
+    let mut parts = s.splitn(3, ':');
+    let (Some(kind), Some(name), Some(value)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(format!("invalid hint `{s}`, expected TYPE:NAME:VALUE"));
+    };
+
+    let invalid = || format!("invalid {kind} value `{value}` for hint `{name}`");
+    let value = match kind {
+        "int" => HintValue::Int(value.parse().map_err(|_| invalid())?),
+        "double" => HintValue::Double(value.parse().map_err(|_| invalid())?),
+        "string" => HintValue::Str(value.to_string()),
+        "byte" => HintValue::Byte(value.parse().map_err(|_| invalid())?),
+        "boolean" => HintValue::Bool(value.parse().map_err(|_| invalid())?),
+        _ => {
+            return Err(format!(
+                "invalid hint type `{kind}`, expected one of int, double, string, byte, boolean"
+            ));
+        }
+    };
+
+    Ok((name.to_string(), value))
+}
+
+pub async fn handle_compat(
+    args: NotifySendArgs,
+    quiet_hours: &[TimeWindow],
+) -> Result<(), Box<dyn Error>> {
+    let notification = args.to_notification();
+
+    let mut sender = Sender::default();
+    // Subscribe before sending so a fast close can't be missed. Without a bus there is
+    // nothing to wait for, and sending spools the notification or reports why it can't.
+    let mut signals = None;
+    if args.wait
+        && let Ok(proxy) = sender.proxy().await
+    {
+        signals = Some((
+            proxy.receive_action_invoked().await?,
+            proxy.receive_notification_closed().await?,
+        ));
+    }
+
+    let id = match sender
+        .send(&notification, quiet_hours, &Throttle::default())
+        .await?
+    {
+        Outcome::Sent(id) => id,
+        Outcome::Deferred => {
+            eprintln!("Do Not Disturb is active: notification deferred.");
+            return Ok(());
+        }
+        Outcome::Spooled => {
+            eprintln!("No notification server is reachable: notification spooled.");
+            return Ok(());
+        }
+        outcome => unreachable!("{outcome:?} without a throttle"),
+    };
+    if args.print_id {
+        println!("{id}");
+    }
+
+    let Some((mut invoked, mut closed)) = signals else {
+        return Ok(());
+    };
+
+    loop {
+        tokio::select! {
+            // Servers close a notification right after its action, so when both signals are
+            // waiting the action has to win.
+            biased;
+            Some(signal) = invoked.next() => {
+                let signal = signal.args()?;
+                if signal.id == id {
+                    record_action(id, signal.action_key);
+                    println!("{}", signal.action_key);
+                    return Ok(());
+                }
+            }
+            Some(signal) = closed.next() => {
+                let signal = signal.args()?;
+                if signal.id == id {
+                    record_closed(id, signal.reason);
+                    return Ok(());
+                }
+            }
+            else => return Ok(()),
+        }
+    }
+}
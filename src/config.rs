@@ -0,0 +1,167 @@
+use crate::clock::TimeWindow;
+use crate::duration::parse_timeout;
+use crate::notification::{DEFAULT_APP_NAME, DEFAULT_ICON, DEFAULT_TIMEOUT, Notification, Urgency};
+use crate::paths::config_dir;
+use crate::recurring::Reminder;
+use crate::sysmon::MonitorConfig;
+use crate::tail::TailRule;
+use crate::throttle::RateLimit;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::env;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+
+/// Notification fields that can be given a default outside the command line.
+#[derive(Clone, Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NotificationDefaults {
+    pub app_name: Option<String>,
+    pub icon: Option<String>,
+    #[serde(default, deserialize_with = "crate::duration::deserialize_timeout")]
+    pub timeout: Option<i32>,
+    pub urgency: Option<Urgency>,
+}
+
+impl NotificationDefaults {
+    /// Keeps the fields set on `self` and fills the rest from `fallback`.
+    pub fn or(self, fallback: NotificationDefaults) -> NotificationDefaults {
+        NotificationDefaults {
+            app_name: self.app_name.or(fallback.app_name),
+            icon: self.icon.or(fallback.icon),
+            timeout: self.timeout.or(fallback.timeout),
+            urgency: self.urgency.or(fallback.urgency),
+        }
+    }
+
+    /// Reads `ALERTIFY_APP_NAME`, `ALERTIFY_ICON`, `ALERTIFY_TIMEOUT` and `ALERTIFY_URGENCY`.
+    pub fn from_env() -> Result<NotificationDefaults, Box<dyn Error>> {
+        let var = |name: &str| env::var(name).ok().filter(|value| !value.is_empty());
+
+        let timeout = match var("ALERTIFY_TIMEOUT") {
+            Some(value) => {
+                Some(parse_timeout(&value).map_err(|e| format!("invalid ALERTIFY_TIMEOUT: {e}"))?)
+            }
+            None => None,
+        };
+        let urgency = match var("ALERTIFY_URGENCY") {
+            Some(value) => Some(
+                <Urgency as clap::ValueEnum>::from_str(&value, true)
+                    .map_err(|_| format!("invalid ALERTIFY_URGENCY `{value}`"))?,
+            ),
+            None => None,
+        };
+
+        Ok(NotificationDefaults {
+            app_name: var("ALERTIFY_APP_NAME"),
+            icon: var("ALERTIFY_ICON"),
+            timeout,
+            urgency,
+        })
+    }
+
+    /// Builds a notification from these defaults, using the built-in ones for unset fields.
+    pub fn to_notification(&self, title: String, body: String) -> Notification {
+        let mut notification = Notification::new(
+            self.app_name
+                .clone()
+                .unwrap_or_else(|| String::from(DEFAULT_APP_NAME)),
+            0,
+            title,
+            body,
+            self.icon
+                .clone()
+                .unwrap_or_else(|| String::from(DEFAULT_ICON)),
+            self.timeout.unwrap_or(DEFAULT_TIMEOUT),
+        );
+        notification.urgency = self.urgency.unwrap_or_default();
+        notification
+    }
+}
+
+/// The user's `config.toml`.
+///
+/// ```toml
+/// spool = true
+///
+/// [defaults]
+/// app_name = "scripts"
+///
+/// [profile.laptop]
+/// timeout = "10s"
+///
+/// [rate_limit.backup]
+/// max = 3
+/// per = "10m"
+///
+/// [[quiet_hours]]
+/// start = "22:00"
+/// end = "07:00"
+///
+/// [[reminder]]
+/// title = "Stand-up"
+/// schedule = "every weekday at 09:55"
+/// ```
+#[derive(Debug, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub defaults: NotificationDefaults,
+    pub profile: HashMap<String, NotificationDefaults>,
+    /// Per-application rate limits, keyed by app name.
+    pub rate_limit: HashMap<String, RateLimit>,
+    pub quiet_hours: Vec<TimeWindow>,
+    /// Recurring reminders, fired while `alertify jobs run` is running.
+    pub reminder: Vec<Reminder>,
+    /// Rules applied by `alertify tail`.
+    pub tail_rule: Vec<TailRule>,
+    /// Thresholds for `alertify monitor-system`.
+    pub monitor: MonitorConfig,
+    /// Keep notifications that found no notification server and deliver them later.
+    pub spool: bool,
+}
+
+impl Config {
+    pub fn default_path() -> PathBuf {
+        config_dir().join("config.toml")
+    }
+
+    /// Loads the config at `path`, or the default location when `None`.
+    ///
+    /// A missing file at the default location is not an error.
+    pub fn load(path: Option<&Path>) -> Result<Config, Box<dyn Error>> {
+        let (path, required) = match path {
+            Some(path) => (path.to_path_buf(), true),
+            None => (Config::default_path(), false),
+        };
+
+        let source = match std::fs::read_to_string(&path) {
+            Ok(source) => source,
+            Err(e) if !required && e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(Config::default());
+            }
+            Err(e) => return Err(format!("failed to read {}: {e}", path.display()).into()),
+        };
+
+        Ok(toml::from_str(&source).map_err(|e| format!("invalid {}: {e}", path.display()))?)
+    }
+
+    /// Resolves the defaults for `profile`, in order of precedence: environment,
+    /// profile, then the global `[defaults]` table.
+    pub fn resolve_defaults(
+        &self,
+        profile: Option<&str>,
+    ) -> Result<NotificationDefaults, Box<dyn Error>> {
+        let profile = match profile {
+            Some(name) => self
+                .profile
+                .get(name)
+                .cloned()
+                .ok_or_else(|| format!("unknown profile `{name}`"))?,
+            None => NotificationDefaults::default(),
+        };
+
+        Ok(NotificationDefaults::from_env()?
+            .or(profile)
+            .or(self.defaults.clone()))
+    }
+}
@@ -0,0 +1,426 @@
+use crate::bus;
+use crate::clock::TimeWindow;
+use crate::config::{Config, NotificationDefaults};
+use crate::deliver::deliver;
+use crate::history::{Fate, SentNotifications, close_reason_name};
+use crate::notification::{
+    ActionInvokedStream, Notification, NotificationClosedStream, NotificationsProxy,
+};
+use crate::paths::runtime_dir;
+use crate::spec::NotificationSpec;
+use crate::throttle::{Outcome, RateLimit, Throttle};
+use chrono::{DateTime, Local};
+use clap::Args;
+use futures_lite::StreamExt;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{UnixListener, UnixStream};
+use tokio::sync::{broadcast, mpsc};
+
+/// Events kept for a subscriber that falls behind before older ones are dropped.
+const EVENT_BACKLOG: usize = 256;
+
+/// Lines queued for a client that isn't reading before the daemon waits for it.
+const CLIENT_BACKLOG: usize = 64;
+
+#[derive(Args, Debug)]
+pub struct DaemonArgs {
+    /// Socket to listen on, by default `$XDG_RUNTIME_DIR/alertify.sock`
+    #[arg(long)]
+    pub socket: Option<PathBuf>,
+}
+
+pub fn default_socket() -> PathBuf {
+    runtime_dir().join("alertify.sock")
+}
+
+/// One line from a client.
+#[derive(Debug, Deserialize)]
+#[serde(tag = "op", rename_all = "snake_case", deny_unknown_fields)]
+enum Request {
+    Send {
+        notification: NotificationSpec,
+    },
+    /// Replaces the content of a notification the daemon sent.
+    Update {
+        id: u32,
+        notification: NotificationSpec,
+    },
+    Close {
+        id: u32,
+    },
+    /// Lists the daemon's notifications that are still open.
+    List,
+    /// Streams action and close events for the daemon's notifications.
+    Subscribe,
+}
+
+/// The answer to one request, written as one line in request order.
+#[derive(Debug, Default, Serialize)]
+struct Reply {
+    ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    id: Option<u32>,
+    /// `sent`, `duplicate`, `collapsed`, `deferred` or `spooled`.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    outcome: Option<&'static str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    notifications: Option<Vec<Active>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    error: Option<String>,
+}
+
+impl Reply {
+    fn ok() -> Reply {
+        Reply {
+            ok: true,
+            ..Reply::default()
+        }
+    }
+
+    fn error(error: String) -> Reply {
+        Reply {
+            error: Some(error),
+            ..Reply::default()
+        }
+    }
+}
+
+/// A notification sent through the daemon that hasn't been closed yet.
+#[derive(Clone, Debug, Serialize)]
+struct Active {
+    id: u32,
+    app_name: String,
+    title: String,
+    sent_at: DateTime<Local>,
+}
+
+/// Pushed to subscribed clients, told apart from replies by its `event` field.
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+enum Event {
+    Action {
+        id: u32,
+        action_key: String,
+    },
+    Closed {
+        id: u32,
+        reason: u32,
+        reason_name: &'static str,
+    },
+    /// The subscriber fell behind and `missed` events were dropped.
+    Lagged {
+        missed: u64,
+    },
+}
+
+/// The daemon's notifications, kept under one lock so a signal is never matched against
+/// a half-recorded send.
+#[derive(Default)]
+struct Notifications {
+    active: HashMap<u32, Active>,
+    sent: SentNotifications,
+}
+
+struct Daemon {
+    proxy: NotificationsProxy<'static>,
+    defaults: NotificationDefaults,
+    rate_limit: HashMap<String, RateLimit>,
+    quiet_hours: Vec<TimeWindow>,
+    notifications: Mutex<Notifications>,
+    events: broadcast::Sender<Event>,
+}
+
+impl Daemon {
+    /// Marks `id` as the daemon's, passing on whatever the server said about it before
+    /// its ID came back.
+    fn track(&self, id: u32, app_name: &str, title: &str) {
+        let notifications = &mut *self.notifications.lock().unwrap();
+        notifications.active.insert(
+            id,
+            Active {
+                id,
+                app_name: app_name.to_string(),
+                title: title.to_string(),
+                sent_at: Local::now(),
+            },
+        );
+        for fate in notifications.sent.insert(id) {
+            self.announce(&mut notifications.active, id, &fate);
+        }
+    }
+
+    /// Records and broadcasts what happened to one of the daemon's notifications.
+    fn announce(&self, active: &mut HashMap<u32, Active>, id: u32, fate: &Fate) {
+        fate.record(id);
+        let event = match fate {
+            Fate::Action(action_key) => Event::Action {
+                id,
+                action_key: action_key.clone(),
+            },
+            Fate::Closed(reason) => {
+                active.remove(&id);
+                Event::Closed {
+                    id,
+                    reason: *reason,
+                    reason_name: close_reason_name(*reason),
+                }
+            }
+        };
+        let _ = self.events.send(event);
+    }
+
+    /// Replies with `outcome`, tracking the notification if it was shown.
+    fn reply(&self, outcome: Outcome, notification: &Notification) -> Reply {
+        let (id, outcome) = match outcome {
+            Outcome::Sent(id) => (Some(id), "sent"),
+            Outcome::Collapsed { id, .. } => (Some(id), "collapsed"),
+            Outcome::Duplicate { .. } => (None, "duplicate"),
+            Outcome::Deferred => (None, "deferred"),
+            Outcome::Spooled => (None, "spooled"),
+        };
+        if let Some(id) = id {
+            self.track(id, &notification.app_name, &notification.title);
+        }
+        Reply {
+            id,
+            outcome: Some(outcome),
+            ..Reply::ok()
+        }
+    }
+
+    async fn send(&self, spec: NotificationSpec) -> Result<Reply, String> {
+        let notification = spec.into_notification(&self.defaults);
+        let throttle = Throttle {
+            rate_limit: self.rate_limit.get(&notification.app_name).copied(),
+            ..Throttle::default()
+        };
+        let outcome = deliver(&self.proxy, &notification, &self.quiet_hours, &throttle)
+            .await
+            .map_err(|e| e.to_string())?;
+        Ok(self.reply(outcome, &notification))
+    }
+
+    async fn update(&self, id: u32, spec: NotificationSpec) -> Result<Reply, String> {
+        if !self.notifications.lock().unwrap().active.contains_key(&id) {
+            return Err(format!("no open notification with ID {id}"));
+        }
+        let mut notification = spec.into_notification(&self.defaults);
+        notification.replaces_id = id;
+        // Not throttled: collapsing would point it at another notification.
+        let outcome = deliver(
+            &self.proxy,
+            &notification,
+            &self.quiet_hours,
+            &Throttle::default(),
+        )
+        .await
+        .map_err(|e| e.to_string())?;
+        if let Outcome::Sent(new_id) = outcome
+            && new_id != id
+        {
+            self.notifications.lock().unwrap().active.remove(&id);
+        }
+        Ok(self.reply(outcome, &notification))
+    }
+
+    async fn handle(&self, request: Request) -> Reply {
+        let result = match request {
+            Request::Send { notification } => self.send(notification).await,
+            Request::Update { id, notification } => self.update(id, notification).await,
+            Request::Close { id } => self
+                .proxy
+                .close_notification(id)
+                .await
+                .map(|()| Reply::ok())
+                .map_err(|e| e.to_string()),
+            Request::List => {
+                let mut notifications: Vec<Active> = self
+                    .notifications
+                    .lock()
+                    .unwrap()
+                    .active
+                    .values()
+                    .cloned()
+                    .collect();
+                notifications.sort_by_key(|active| active.id);
+                Ok(Reply {
+                    notifications: Some(notifications),
+                    ..Reply::ok()
+                })
+            }
+            // Subscriptions belong to the connection, so `serve_client` handles them.
+            Request::Subscribe => Ok(Reply::ok()),
+        };
+        result.unwrap_or_else(Reply::error)
+    }
+
+    /// Turns the server's signals about our notifications into events, until the bus
+    /// goes away.
+    async fn forward_signals(
+        &self,
+        mut invoked: ActionInvokedStream,
+        mut closed: NotificationClosedStream,
+    ) -> Result<(), Box<dyn Error>> {
+        loop {
+            let (id, fate) = tokio::select! {
+                // An action comes before the close it causes, and the close forgets the
+                // notification.
+                biased;
+                Some(signal) = invoked.next() => {
+                    let signal = signal.args()?;
+                    (signal.id, Fate::Action(signal.action_key.to_string()))
+                }
+                Some(signal) = closed.next() => {
+                    let signal = signal.args()?;
+                    (signal.id, Fate::Closed(signal.reason))
+                }
+                else => return Err("the session bus went away".into()),
+            };
+            let notifications = &mut *self.notifications.lock().unwrap();
+            if notifications.sent.signalled(id, &fate) {
+                self.announce(&mut notifications.active, id, &fate);
+            }
+        }
+    }
+}
+
+fn to_line(value: &impl Serialize) -> String {
+    let mut line = serde_json::to_string(value).unwrap_or_default();
+    line.push('\n');
+    line
+}
+
+/// Answers one client's requests in order, interleaving events once it subscribed.
+async fn serve_client(daemon: Arc<Daemon>, stream: UnixStream) {
+    let (reader, mut writer) = stream.into_split();
+    let (lines_out, mut outgoing) = mpsc::channel::<String>(CLIENT_BACKLOG);
+    let writing = tokio::spawn(async move {
+        while let Some(line) = outgoing.recv().await {
+            if writer.write_all(line.as_bytes()).await.is_err() {
+                break;
+            }
+        }
+    });
+
+    let mut subscription = None;
+    let mut lines = BufReader::new(reader).lines();
+    while let Ok(Some(line)) = lines.next_line().await {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let request = match serde_json::from_str::<Request>(&line) {
+            Ok(request) => request,
+            Err(e) => {
+                let reply = Reply::error(format!("invalid request: {e}"));
+                let _ = lines_out.send(to_line(&reply)).await;
+                continue;
+            }
+        };
+        if matches!(request, Request::Subscribe) && subscription.is_none() {
+            let mut events = daemon.events.subscribe();
+            let lines_out = lines_out.clone();
+            subscription = Some(tokio::spawn(async move {
+                loop {
+                    let event = match events.recv().await {
+                        Ok(event) => event,
+                        Err(broadcast::error::RecvError::Lagged(missed)) => {
+                            Event::Lagged { missed }
+                        }
+                        Err(broadcast::error::RecvError::Closed) => break,
+                    };
+                    if lines_out.send(to_line(&event)).await.is_err() {
+                        break;
+                    }
+                }
+            }));
+        }
+        let _ = lines_out.send(to_line(&daemon.handle(request).await)).await;
+    }
+
+    if let Some(subscription) = subscription {
+        subscription.abort();
+    }
+    drop(lines_out);
+    let _ = writing.await;
+}
+
+/// Removes the socket when the daemon stops.
+struct SocketGuard(PathBuf);
+
+impl Drop for SocketGuard {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+/// Binds the socket, taking over a stale one left by a daemon that didn't exit cleanly.
+fn bind(path: &Path) -> Result<(UnixListener, SocketGuard), Box<dyn Error>> {
+    if path.exists() {
+        if std::os::unix::net::UnixStream::connect(path).is_ok() {
+            return Err(
+                format!("another daemon is already listening on {}", path.display()).into(),
+            );
+        }
+        fs::remove_file(path)?;
+    }
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    // Created accessible to us alone, rather than narrowed afterwards, so nobody else can
+    // connect in between.
+    // SAFETY: umask has no memory-safety preconditions and can't fail.
+    let umask = unsafe { libc::umask(0o177) };
+    let listener = UnixListener::bind(path);
+    // SAFETY: as above.
+    unsafe { libc::umask(umask) };
+    let listener = listener.map_err(|e| format!("failed to listen on {}: {e}", path.display()))?;
+    Ok((listener, SocketGuard(path.to_path_buf())))
+}
+
+/// Keeps one bus connection open and answers newline-delimited JSON requests on a Unix
+/// socket until interrupted.
+pub async fn handle_daemon(
+    args: DaemonArgs,
+    defaults: &NotificationDefaults,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let connection = bus::session().await?;
+    let proxy = NotificationsProxy::new(&connection).await?;
+    // Subscribed before any client can send.
+    let invoked = proxy.receive_action_invoked().await?;
+    let closed = proxy.receive_notification_closed().await?;
+    let daemon = Arc::new(Daemon {
+        proxy,
+        defaults: defaults.clone(),
+        rate_limit: config.rate_limit.clone(),
+        quiet_hours: config.quiet_hours.clone(),
+        notifications: Mutex::new(Notifications::default()),
+        events: broadcast::channel(EVENT_BACKLOG).0,
+    });
+
+    let path = args.socket.unwrap_or_else(default_socket);
+    let (listener, _guard) = bind(&path)?;
+    eprintln!("Listening on {}, press Ctrl-C to stop.", path.display());
+
+    let accepting = async {
+        loop {
+            match listener.accept().await {
+                Ok((stream, _)) => tokio::spawn(serve_client(Arc::clone(&daemon), stream)),
+                Err(e) => return e,
+            };
+        }
+    };
+
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        result = daemon.forward_signals(invoked, closed) => result?,
+        error = accepting => return Err(error.into()),
+    }
+    Ok(())
+}
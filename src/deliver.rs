@@ -0,0 +1,118 @@
+use crate::bus;
+use crate::clock::TimeWindow;
+use crate::dnd::{active_reason, defer, deliver_digest};
+use crate::jobs::ensure_scheduler;
+use crate::notification::{Notification, NotificationsProxy, Urgency};
+use crate::spool;
+use crate::throttle::{Outcome, Throttle, notify_throttled};
+use std::error::Error;
+
+/// Sends `notification` unless do-not-disturb or `throttle` holds it back, without
+/// spooling it when no server is reachable.
+///
+/// Critical notifications bypass do-not-disturb; otherwise any deferred notifications are
+/// delivered as a digest first once it has ended. A deferred notification starts the
+/// scheduler, which delivers the digest when do-not-disturb ends.
+pub async fn try_deliver(
+    proxy: &NotificationsProxy<'_>,
+    notification: &Notification,
+    quiet_hours: &[TimeWindow],
+    throttle: &Throttle,
+) -> Result<Outcome, Box<dyn Error>> {
+    let held_back = active_reason(quiet_hours)?.is_some();
+    if held_back && notification.urgency != Urgency::Critical {
+        defer(notification).await?;
+        ensure_scheduler()?;
+        return Ok(Outcome::Deferred);
+    }
+    if !held_back {
+        deliver_digest(proxy).await?;
+    }
+    notify_throttled(proxy, notification, throttle).await
+}
+
+/// Like `try_deliver`, but spools `notification` when no server is reachable and the
+/// spool is enabled.
+pub async fn deliver(
+    proxy: &NotificationsProxy<'_>,
+    notification: &Notification,
+    quiet_hours: &[TimeWindow],
+    throttle: &Throttle,
+) -> Result<Outcome, Box<dyn Error>> {
+    let result = try_deliver(proxy, notification, quiet_hours, throttle).await;
+    spool_if_unreachable(result, notification)
+}
+
+fn spool_if_unreachable(
+    result: Result<Outcome, Box<dyn Error>>,
+    notification: &Notification,
+) -> Result<Outcome, Box<dyn Error>> {
+    match result {
+        Err(e) if spool::is_enabled() && spool::is_unreachable(e.as_ref()) => {
+            spool::spool(notification)?;
+            Ok(Outcome::Spooled)
+        }
+        result => result,
+    }
+}
+
+/// Sends notifications over a session bus connection that is opened on first use and
+/// opened again after it breaks, so commands that run for a long time keep working, or
+/// keep spooling, while the bus or the server comes and goes.
+#[derive(Default)]
+pub struct Sender {
+    proxy: Option<NotificationsProxy<'static>>,
+}
+
+impl Sender {
+    /// The proxy for the notification server, connecting if not connected yet.
+    pub async fn proxy(&mut self) -> zbus::Result<&NotificationsProxy<'static>> {
+        if self.proxy.is_none() {
+            let connection = bus::session().await?;
+            self.proxy = Some(NotificationsProxy::new(&connection).await?);
+        }
+        Ok(self.proxy.as_ref().expect("connected above"))
+    }
+
+    /// Delivers `notification` after anything spooled earlier.
+    pub async fn send(
+        &mut self,
+        notification: &Notification,
+        quiet_hours: &[TimeWindow],
+        throttle: &Throttle,
+    ) -> Result<Outcome, Box<dyn Error>> {
+        let result = async {
+            let proxy = self.proxy().await?;
+            // Anything spooled earlier goes first, so notifications arrive in order.
+            if spool::pending()? > 0
+                && let Err(e) = spool::flush(proxy, quiet_hours).await
+            {
+                if spool::is_unreachable(e.as_ref()) {
+                    return Err(e);
+                }
+                eprintln!("alertify: failed to deliver spooled notifications: {e}");
+            }
+            try_deliver(proxy, notification, quiet_hours, throttle).await
+        }
+        .await;
+
+        if let Err(e) = &result
+            && let Some(zbus::Error::InputOutput(_)) = e.downcast_ref::<zbus::Error>()
+        {
+            // The bus went away; connect again next time.
+            self.proxy = None;
+        }
+        spool_if_unreachable(result, notification)
+    }
+}
+
+/// Connects to the session bus and delivers `notification`, after anything spooled earlier.
+pub async fn send_notification(
+    notification: Notification,
+    quiet_hours: &[TimeWindow],
+    throttle: &Throttle,
+) -> Result<Outcome, Box<dyn Error>> {
+    Sender::default()
+        .send(&notification, quiet_hours, throttle)
+        .await
+}
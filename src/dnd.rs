@@ -0,0 +1,265 @@
+use crate::bus;
+use crate::clock::TimeWindow;
+use crate::notification::{Notification, NotificationsProxy, notify};
+use crate::paths::state_dir;
+use chrono::{DateTime, Local};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+
+/// Lines of the digest body before the rest are summarised as "and N more".
+const DIGEST_LINES: usize = 10;
+
+#[derive(Clone, Copy, Debug, ValueEnum)]
+pub enum DndAction {
+    On,
+    Off,
+    Status,
+}
+
+/// Why notifications are currently being held back.
+#[derive(Debug, PartialEq, Eq)]
+pub enum DndReason {
+    Manual,
+    QuietHours,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DndState {
+    enabled: bool,
+    since: Option<DateTime<Local>>,
+    /// The process that turned it on for as long as it runs, e.g. a pomodoro.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    owner: Option<u32>,
+}
+
+/// A notification that arrived while do-not-disturb was active.
+#[derive(Debug, Serialize, Deserialize)]
+struct Deferred {
+    timestamp: DateTime<Local>,
+    notification: Notification,
+}
+
+fn state_path() -> PathBuf {
+    state_dir().join("dnd.json")
+}
+
+fn outbox_path() -> PathBuf {
+    state_dir().join("dnd-outbox.jsonl")
+}
+
+fn load_state() -> Result<DndState, Box<dyn Error>> {
+    match fs::read_to_string(state_path()) {
+        Ok(source) => Ok(serde_json::from_str(&source)?),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(DndState::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// The state in effect, where a setting whose owner has died no longer counts.
+fn current_state() -> Result<DndState, Box<dyn Error>> {
+    let state = load_state()?;
+    match state.owner {
+        Some(pid) if !Path::new("/proc").join(pid.to_string()).exists() => Ok(DndState::default()),
+        _ => Ok(state),
+    }
+}
+
+fn save_state(state: &DndState) -> Result<(), Box<dyn Error>> {
+    fs::create_dir_all(state_dir())?;
+    fs::write(state_path(), serde_json::to_vec(state)?)?;
+    Ok(())
+}
+
+/// Turns manual do-not-disturb on or off, returning the previous setting.
+pub fn set_enabled(enabled: bool) -> Result<bool, Box<dyn Error>> {
+    let state = current_state()?;
+    if state.enabled != enabled || state.owner.is_some() {
+        save_state(&DndState {
+            enabled,
+            since: enabled.then(Local::now),
+            owner: None,
+        })?;
+    }
+    Ok(state.enabled)
+}
+
+/// Turns do-not-disturb on until `release` or until this process exits, whichever comes
+/// first. Returns false, and changes nothing, if it was already on.
+pub fn hold() -> Result<bool, Box<dyn Error>> {
+    if current_state()?.enabled {
+        return Ok(false);
+    }
+    save_state(&DndState {
+        enabled: true,
+        since: Some(Local::now()),
+        owner: Some(std::process::id()),
+    })?;
+    Ok(true)
+}
+
+/// Turns off do-not-disturb turned on by `hold`, unless it was switched since.
+pub fn release() -> Result<(), Box<dyn Error>> {
+    if load_state()?.owner == Some(std::process::id()) {
+        save_state(&DndState::default())?;
+    }
+    Ok(())
+}
+
+pub fn active_reason(quiet_hours: &[TimeWindow]) -> Result<Option<DndReason>, Box<dyn Error>> {
+    if current_state()?.enabled {
+        return Ok(Some(DndReason::Manual));
+    }
+    let now = Local::now();
+    if quiet_hours.iter().any(|period| period.contains(now)) {
+        return Ok(Some(DndReason::QuietHours));
+    }
+    Ok(None)
+}
+
+/// Opens the outbox under an exclusive lock, waiting for it on a blocking thread so the
+/// long-running commands don't stall while another alertify holds it.
+async fn open_outbox() -> Result<File, Box<dyn Error>> {
+    fs::create_dir_all(state_dir())?;
+    let file = OpenOptions::new()
+        .read(true)
+        .append(true)
+        .create(true)
+        .open(outbox_path())?;
+    let file = tokio::task::spawn_blocking(move || file.lock().map(|()| file)).await??;
+    Ok(file)
+}
+
+pub async fn defer(notification: &Notification) -> Result<(), Box<dyn Error>> {
+    let mut line = serde_json::to_string(&Deferred {
+        timestamp: Local::now(),
+        notification: notification.clone(),
+    })?;
+    line.push('\n');
+    open_outbox().await?.write_all(line.as_bytes())?;
+    Ok(())
+}
+
+pub fn has_deferred() -> bool {
+    fs::metadata(outbox_path()).is_ok_and(|meta| meta.len() > 0)
+}
+
+fn digest(deferred: &[Deferred]) -> Notification {
+    let mut lines: Vec<String> = deferred
+        .iter()
+        .take(DIGEST_LINES)
+        .map(|item| {
+            format!(
+                "{} {}: {}",
+                item.timestamp.format("%H:%M"),
+                item.notification.app_name,
+                item.notification.title
+            )
+        })
+        .collect();
+    if deferred.len() > DIGEST_LINES {
+        lines.push(format!("and {} more", deferred.len() - DIGEST_LINES));
+    }
+
+    let mut notification = Notification::new(
+        String::from("alertify"),
+        0,
+        format!(
+            "{} notification{} while Do Not Disturb was on",
+            deferred.len(),
+            if deferred.len() == 1 { "" } else { "s" }
+        ),
+        lines.join("\n"),
+        String::from("appointment-missed"),
+        0,
+    );
+    notification.actions.clear();
+    notification
+}
+
+/// Sends everything in the outbox as a single digest and empties it.
+pub async fn deliver_digest(proxy: &NotificationsProxy<'_>) -> Result<usize, Box<dyn Error>> {
+    if !has_deferred() {
+        return Ok(0);
+    }
+
+    let file = open_outbox().await?;
+    let mut deferred = Vec::new();
+    for line in BufReader::new(&file).lines() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str::<Deferred>(&line) {
+            Ok(item) => deferred.push(item),
+            Err(e) => eprintln!("alertify: skipping unreadable outbox entry: {e}"),
+        }
+    }
+    if deferred.is_empty() {
+        file.set_len(0)?;
+        return Ok(0);
+    }
+
+    notify(proxy, &digest(&deferred)).await?;
+    file.set_len(0)?;
+
+    Ok(deferred.len())
+}
+
+fn outbox_len() -> Result<usize, Box<dyn Error>> {
+    match File::open(outbox_path()) {
+        Ok(file) => Ok(BufReader::new(file)
+            .lines()
+            .filter(|line| line.as_ref().is_ok_and(|line| !line.trim().is_empty()))
+            .count()),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(0),
+        Err(e) => Err(e.into()),
+    }
+}
+
+pub async fn handle_dnd(
+    action: DndAction,
+    quiet_hours: &[TimeWindow],
+) -> Result<(), Box<dyn Error>> {
+    match action {
+        DndAction::On => {
+            set_enabled(true)?;
+            println!("Do Not Disturb is on.");
+        }
+        DndAction::Off => {
+            set_enabled(false)?;
+            if active_reason(quiet_hours)? == Some(DndReason::QuietHours) {
+                println!("Do Not Disturb is off, but quiet hours are still active.");
+                return Ok(());
+            }
+            println!("Do Not Disturb is off.");
+
+            let connection = bus::session().await?;
+            let proxy = NotificationsProxy::new(&connection).await?;
+            let delivered = deliver_digest(&proxy).await?;
+            if delivered > 0 {
+                println!("Delivered a digest of {delivered} deferred notifications.");
+            }
+        }
+        DndAction::Status => {
+            let state = current_state()?;
+            match active_reason(quiet_hours)? {
+                Some(DndReason::Manual) => match state.since {
+                    Some(since) => println!(
+                        "Do Not Disturb is on since {}.",
+                        since.format("%Y-%m-%d %H:%M")
+                    ),
+                    None => println!("Do Not Disturb is on."),
+                },
+                Some(DndReason::QuietHours) => println!("Quiet hours are active."),
+                None => println!("Do Not Disturb is off."),
+            }
+            println!("{} notifications waiting in the outbox.", outbox_len()?);
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,185 @@
+use serde::{Deserialize, Deserializer};
+use std::time::Duration;
+
+/// Parses a duration such as `90s`, `5m` or `1h30m`. Units are `ms`, `s`, `m`, `h` and `d`.
+pub fn parse_duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("invalid duration `{s}`, expected something like 30s, 5m or 1h30m");
+
+    let text = s.trim();
+    if text.is_empty() {
+        return Err(invalid());
+    }
+    if matches!(text, "never" | "default") {
+        return Err(format!(
+            "`{text}` is only accepted for timeouts, give a duration such as 30s or 5m"
+        ));
+    }
+
+    let mut total = Duration::ZERO;
+    let mut rest = text;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        if digits == 0 {
+            return Err(invalid());
+        }
+        let amount: u64 = rest[..digits].parse().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+
+        let unit_len = rest
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(rest.len());
+        let unit = match &rest[..unit_len] {
+            "ms" => Duration::from_millis(1),
+            "s" => Duration::from_secs(1),
+            "m" => Duration::from_secs(60),
+            "h" => Duration::from_secs(60 * 60),
+            "d" => Duration::from_secs(24 * 60 * 60),
+            "" => {
+                return Err(format!(
+                    "missing unit in duration `{s}`, e.g. {amount}s or {amount}m"
+                ));
+            }
+            other => {
+                return Err(format!(
+                    "unknown unit `{other}` in duration `{s}`, expected ms, s, m, h or d"
+                ));
+            }
+        };
+        rest = &rest[unit_len..];
+
+        let amount = u32::try_from(amount).map_err(|_| invalid())?;
+        total = unit
+            .checked_mul(amount)
+            .and_then(|part| total.checked_add(part))
+            .ok_or_else(invalid)?;
+    }
+
+    Ok(total)
+}
+
+/// Deserializes a duration written as a string, e.g. `per = "10m"`.
+pub fn deserialize<'de, D>(deserializer: D) -> Result<Duration, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = String::deserialize(deserializer)?;
+    parse_duration(&text).map_err(serde::de::Error::custom)
+}
+
+/// Parses a notification timeout into the milliseconds the spec expects.
+///
+/// Accepts a duration such as `5s` or `2m`, `never` (0, never expire), `default` (-1, the
+/// server decides), or a plain number of milliseconds.
+pub fn parse_timeout(s: &str) -> Result<i32, String> {
+    let text = s.trim();
+    match text {
+        "never" => return Ok(0),
+        "default" => return Ok(-1),
+        _ => {}
+    }
+    if let Ok(millis) = text.parse::<i32>() {
+        if millis < -1 {
+            return Err(format!(
+                "invalid timeout `{s}`, expected a duration, `never` or `default`"
+            ));
+        }
+        return Ok(millis);
+    }
+
+    let duration = parse_duration(text)
+        .map_err(|e| format!("{e}; timeouts may also be `never` or `default`"))?;
+    if duration.is_zero() {
+        return Err(format!(
+            "a timeout of `{s}` would never expire, write `never` instead"
+        ));
+    }
+    i32::try_from(duration.as_millis())
+        .map_err(|_| format!("timeout `{s}` is too long, the most is about 24 days"))
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RawTimeout {
+    Millis(i64),
+    Text(String),
+}
+
+/// Deserializes an optional timeout written as milliseconds or as a string accepted by
+/// [`parse_timeout`], e.g. `timeout = "10s"`.
+pub fn deserialize_timeout<'de, D>(deserializer: D) -> Result<Option<i32>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let text = match Option::<RawTimeout>::deserialize(deserializer)? {
+        None => return Ok(None),
+        Some(RawTimeout::Millis(millis)) => millis.to_string(),
+        Some(RawTimeout::Text(text)) => text,
+    };
+    parse_timeout(&text)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// Formats a duration for people, e.g. `4.2s`, `2m03s` or `1h05m`.
+pub fn format_duration(duration: Duration) -> String {
+    let seconds = duration.as_secs();
+    let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    if hours > 0 {
+        format!("{hours}h{minutes:02}m")
+    } else if minutes > 0 {
+        format!("{minutes}m{seconds:02}s")
+    } else {
+        format!("{:.1}s", duration.as_secs_f64())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_add_up_their_parts() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration(" 250ms "), Ok(Duration::from_millis(250)));
+        assert_eq!(parse_duration("1d"), Ok(Duration::from_secs(86_400)));
+    }
+
+    #[test]
+    fn durations_need_units_and_numbers() {
+        assert!(parse_duration("").is_err());
+        assert!(parse_duration("5").unwrap_err().contains("missing unit"));
+        assert!(
+            parse_duration("5w")
+                .unwrap_err()
+                .contains("unknown unit `w`")
+        );
+        assert!(parse_duration("m").is_err());
+        assert!(
+            parse_duration("never")
+                .unwrap_err()
+                .contains("only accepted for timeouts")
+        );
+        assert!(parse_duration("99999999999s").is_err());
+    }
+
+    #[test]
+    fn timeouts_accept_keywords_millis_and_durations() {
+        assert_eq!(parse_timeout("never"), Ok(0));
+        assert_eq!(parse_timeout("default"), Ok(-1));
+        assert_eq!(parse_timeout("1500"), Ok(1500));
+        assert_eq!(parse_timeout("2m"), Ok(120_000));
+        assert!(parse_timeout("-2").is_err());
+        assert!(parse_timeout("0s").unwrap_err().contains("write `never`"));
+        assert!(parse_timeout("30d").unwrap_err().contains("too long"));
+    }
+
+    #[test]
+    fn formats_the_largest_units() {
+        assert_eq!(format_duration(Duration::from_millis(4200)), "4.2s");
+        assert_eq!(format_duration(Duration::from_secs(123)), "2m03s");
+        assert_eq!(format_duration(Duration::from_secs(3900)), "1h05m");
+    }
+}
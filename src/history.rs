@@ -0,0 +1,450 @@
+use crate::clock::parse_time;
+use crate::notification::{Notification, Urgency};
+use crate::paths::state_dir;
+use chrono::{DateTime, Local};
+use clap::Args;
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::error::Error;
+use std::fs::{self, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::PathBuf;
+use std::time::{Duration, Instant};
+
+/// How long a signal about an unknown ID is kept, since the server can signal an action or
+/// a close before the reply to `Notify` has told us the ID.
+const EARLY_SIGNAL_WINDOW: Duration = Duration::from_secs(5);
+
+/// One line of `history.jsonl`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum HistoryRecord {
+    Sent {
+        timestamp: DateTime<Local>,
+        id: u32,
+        notification: Notification,
+    },
+    Action {
+        timestamp: DateTime<Local>,
+        id: u32,
+        action_key: String,
+    },
+    Closed {
+        timestamp: DateTime<Local>,
+        id: u32,
+        reason: u32,
+    },
+}
+
+/// A sent notification together with what later happened to it.
+#[derive(Debug, Serialize)]
+pub struct HistoryEntry {
+    pub timestamp: DateTime<Local>,
+    pub id: u32,
+    pub notification: Notification,
+    pub action: Option<String>,
+    pub close_reason: Option<&'static str>,
+}
+
+#[derive(Args, Debug)]
+pub struct HistoryArgs {
+    /// Only show notifications from this application
+    #[arg(short, long)]
+    pub app: Option<String>,
+
+    /// Only show notifications with this urgency
+    #[arg(short, long, value_enum)]
+    pub urgency: Option<Urgency>,
+
+    /// Only show notifications sent at or after this time (RFC 3339, `YYYY-MM-DD` or `YYYY-MM-DD HH:MM`)
+    #[arg(long, value_parser = parse_time)]
+    pub since: Option<DateTime<Local>>,
+
+    /// Only show notifications sent before this time
+    #[arg(long, value_parser = parse_time)]
+    pub until: Option<DateTime<Local>>,
+
+    /// Only show notifications whose title or body contains this text (case-insensitive)
+    #[arg(short, long)]
+    pub grep: Option<String>,
+
+    /// Show at most this many of the most recent matches
+    #[arg(short = 'n', long)]
+    pub limit: Option<usize>,
+
+    /// Print one JSON object per line instead of text
+    #[arg(long, default_value_t = false)]
+    pub json: bool,
+}
+
+pub fn history_path() -> PathBuf {
+    state_dir().join("history.jsonl")
+}
+
+pub fn append(record: &HistoryRecord) -> Result<(), Box<dyn Error>> {
+    let path = history_path();
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+
+    let mut line = serde_json::to_string(record)?;
+    line.push('\n');
+
+    // A single write on an O_APPEND file keeps concurrent invocations from interleaving.
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    file.write_all(line.as_bytes())?;
+
+    Ok(())
+}
+
+fn append_or_warn(record: HistoryRecord) {
+    if let Err(e) = append(&record) {
+        eprintln!("alertify: failed to record history: {e}");
+    }
+}
+
+pub fn record_sent(notification: &Notification, id: u32) {
+    append_or_warn(HistoryRecord::Sent {
+        timestamp: Local::now(),
+        id,
+        notification: notification.clone(),
+    });
+}
+
+pub fn record_action(id: u32, action_key: &str) {
+    append_or_warn(HistoryRecord::Action {
+        timestamp: Local::now(),
+        id,
+        action_key: action_key.to_string(),
+    });
+}
+
+pub fn record_closed(id: u32, reason: u32) {
+    append_or_warn(HistoryRecord::Closed {
+        timestamp: Local::now(),
+        id,
+        reason,
+    });
+}
+
+/// What the server signalled about a notification.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Fate {
+    Action(String),
+    Closed(u32),
+}
+
+impl Fate {
+    pub fn record(&self, id: u32) {
+        match self {
+            Fate::Action(action_key) => record_action(id, action_key),
+            Fate::Closed(reason) => record_closed(id, *reason),
+        }
+    }
+}
+
+/// Tells the action and close signals about notifications this process sent apart from
+/// those about everyone else's, for a process that stays connected after sending.
+#[derive(Debug, Default)]
+pub struct SentNotifications {
+    open: HashSet<u32>,
+    /// Signals about IDs not known to be ours, in case they turn out to be.
+    early: Vec<(Instant, u32, Fate)>,
+}
+
+impl SentNotifications {
+    /// Marks `id` as ours and open, returning what was already signalled about it.
+    pub fn insert(&mut self, id: u32) -> Vec<Fate> {
+        self.open.insert(id);
+        let mut fates = Vec::new();
+        self.early.retain(|(_, early_id, fate)| {
+            if *early_id == id {
+                fates.push(fate.clone());
+            }
+            *early_id != id
+        });
+        if fates.iter().any(|fate| matches!(fate, Fate::Closed(_))) {
+            self.open.remove(&id);
+        }
+        fates
+    }
+
+    /// Whether a signal is about one of ours. Others are kept for a while in case their
+    /// ID is still on its way to `insert`.
+    pub fn signalled(&mut self, id: u32, fate: &Fate) -> bool {
+        let now = Instant::now();
+        self.early
+            .retain(|(at, ..)| now.duration_since(*at) < EARLY_SIGNAL_WINDOW);
+        if !self.open.contains(&id) {
+            self.early.push((now, id, fate.clone()));
+            return false;
+        }
+        if matches!(fate, Fate::Closed(_)) {
+            self.open.remove(&id);
+        }
+        true
+    }
+}
+
+/// Describes a `NotificationClosed` reason code.
+pub fn close_reason_name(reason: u32) -> &'static str {
+    match reason {
+        1 => "expired",
+        2 => "dismissed",
+        3 => "closed",
+        _ => "undefined",
+    }
+}
+
+pub fn load_records() -> Result<Vec<HistoryRecord>, Box<dyn Error>> {
+    let path = history_path();
+    let file = match fs::File::open(&path) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(format!("failed to open {}: {e}", path.display()).into()),
+    };
+
+    let mut records = Vec::new();
+    for (index, line) in BufReader::new(file).lines().enumerate() {
+        let line = line?;
+        if line.trim().is_empty() {
+            continue;
+        }
+        match serde_json::from_str(&line) {
+            Ok(record) => records.push(record),
+            Err(e) => eprintln!("alertify: skipping {}:{}: {e}", path.display(), index + 1),
+        }
+    }
+
+    Ok(records)
+}
+
+/// Pairs action and close records with the most recent notification sent under the same ID.
+pub fn entries(records: Vec<HistoryRecord>) -> Vec<HistoryEntry> {
+    let mut entries: Vec<HistoryEntry> = Vec::new();
+    for record in records {
+        match record {
+            HistoryRecord::Sent {
+                timestamp,
+                id,
+                notification,
+            } => entries.push(HistoryEntry {
+                timestamp,
+                id,
+                notification,
+                action: None,
+                close_reason: None,
+            }),
+            HistoryRecord::Action { id, action_key, .. } => {
+                if let Some(entry) = entries.iter_mut().rev().find(|entry| entry.id == id) {
+                    entry.action = Some(action_key);
+                }
+            }
+            HistoryRecord::Closed { id, reason, .. } => {
+                if let Some(entry) = entries.iter_mut().rev().find(|entry| entry.id == id) {
+                    entry.close_reason = Some(close_reason_name(reason));
+                }
+            }
+        }
+    }
+    entries
+}
+
+impl HistoryArgs {
+    fn matches(&self, entry: &HistoryEntry) -> bool {
+        let notification = &entry.notification;
+        if self
+            .app
+            .as_ref()
+            .is_some_and(|app| &notification.app_name != app)
+        {
+            return false;
+        }
+        if self
+            .urgency
+            .is_some_and(|urgency| notification.urgency != urgency)
+        {
+            return false;
+        }
+        if self.since.is_some_and(|since| entry.timestamp < since) {
+            return false;
+        }
+        if self.until.is_some_and(|until| entry.timestamp >= until) {
+            return false;
+        }
+        if let Some(text) = &self.grep {
+            let text = text.to_lowercase();
+            if !notification.title.to_lowercase().contains(&text)
+                && !notification.body.to_lowercase().contains(&text)
+            {
+                return false;
+            }
+        }
+        true
+    }
+}
+
+fn print_entry(entry: &HistoryEntry) {
+    let notification = &entry.notification;
+    let mut line = format!(
+        "{}  #{:<5} {:<8} {}: {}",
+        entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+        entry.id,
+        format!("{:?}", notification.urgency).to_lowercase(),
+        notification.app_name,
+        notification.title
+    );
+    if !notification.body.is_empty() {
+        line.push_str(&format!(" - {}", notification.body.replace('\n', " ")));
+    }
+    if let Some(action) = &entry.action {
+        line.push_str(&format!("  [action: {action}]"));
+    }
+    if let Some(reason) = entry.close_reason {
+        line.push_str(&format!("  [{reason}]"));
+    }
+    println!("{line}");
+}
+
+pub fn handle_history(args: HistoryArgs) -> Result<(), Box<dyn Error>> {
+    let mut matched: Vec<HistoryEntry> = entries(load_records()?)
+        .into_iter()
+        .filter(|entry| args.matches(entry))
+        .collect();
+
+    if let Some(limit) = args.limit {
+        let skip = matched.len().saturating_sub(limit);
+        matched.drain(..skip);
+    }
+
+    for entry in &matched {
+        if args.json {
+            println!("{}", serde_json::to_string(entry)?);
+        } else {
+            print_entry(entry);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn at(hour: u32) -> DateTime<Local> {
+        Local.with_ymd_and_hms(2026, 3, 2, hour, 0, 0).unwrap()
+    }
+
+    fn sent(hour: u32, id: u32, app_name: &str, title: &str) -> HistoryRecord {
+        let mut notification = Notification::new(
+            app_name.to_string(),
+            0,
+            title.to_string(),
+            String::from("Body text"),
+            String::from("dialog-information"),
+            5000,
+        );
+        notification.urgency = Urgency::Normal;
+        HistoryRecord::Sent {
+            timestamp: at(hour),
+            id,
+            notification,
+        }
+    }
+
+    fn filter() -> HistoryArgs {
+        HistoryArgs {
+            app: None,
+            urgency: None,
+            since: None,
+            until: None,
+            grep: None,
+            limit: None,
+            json: false,
+        }
+    }
+
+    #[test]
+    fn pairs_events_with_the_latest_notification_under_an_id() {
+        let records = vec![
+            sent(9, 1, "mail", "first"),
+            sent(10, 1, "mail", "reused id"),
+            HistoryRecord::Action {
+                timestamp: at(10),
+                id: 1,
+                action_key: String::from("open"),
+            },
+            HistoryRecord::Closed {
+                timestamp: at(10),
+                id: 1,
+                reason: 2,
+            },
+            HistoryRecord::Closed {
+                timestamp: at(11),
+                id: 7,
+                reason: 1,
+            },
+        ];
+        let entries = entries(records);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].action, None);
+        assert_eq!(entries[1].action.as_deref(), Some("open"));
+        assert_eq!(entries[1].close_reason, Some("dismissed"));
+    }
+
+    #[test]
+    fn signals_before_the_id_is_known_are_kept() {
+        let mut sent = SentNotifications::default();
+        assert!(!sent.signalled(4, &Fate::Action(String::from("open"))));
+        assert!(!sent.signalled(4, &Fate::Closed(2)));
+        assert!(!sent.signalled(9, &Fate::Closed(1)));
+        assert_eq!(
+            sent.insert(4),
+            [Fate::Action(String::from("open")), Fate::Closed(2)]
+        );
+        assert!(!sent.signalled(4, &Fate::Closed(3)), "already closed");
+
+        assert!(sent.insert(5).is_empty());
+        assert!(sent.signalled(5, &Fate::Action(String::from("default"))));
+        assert!(sent.signalled(5, &Fate::Closed(2)));
+        assert!(!sent.signalled(5, &Fate::Closed(2)));
+    }
+
+    #[test]
+    fn filters_by_app_time_and_text() {
+        let entries = entries(vec![
+            sent(8, 1, "mail", "Inbox"),
+            sent(12, 2, "backup", "Nightly backup done"),
+        ]);
+
+        let by_app = HistoryArgs {
+            app: Some(String::from("backup")),
+            ..filter()
+        };
+        assert!(!by_app.matches(&entries[0]));
+        assert!(by_app.matches(&entries[1]));
+
+        let window = HistoryArgs {
+            since: Some(at(8)),
+            until: Some(at(12)),
+            ..filter()
+        };
+        assert!(window.matches(&entries[0]));
+        assert!(!window.matches(&entries[1]), "`until` is exclusive");
+
+        let grep = HistoryArgs {
+            grep: Some(String::from("NIGHTLY")),
+            ..filter()
+        };
+        assert!(!grep.matches(&entries[0]));
+        assert!(grep.matches(&entries[1]));
+
+        let urgency = HistoryArgs {
+            urgency: Some(Urgency::Critical),
+            ..filter()
+        };
+        assert!(!urgency.matches(&entries[0]));
+    }
+}
@@ -9,6 +9,7 @@ use super::international::STD_INTERNATIONAL_ICONS;
 use super::mime::STD_MIME_TYPE_ICONS;
 use super::place::STD_PLACE_ICONS;
 use super::status::STD_STATUS_ICONS;
+use super::types::IconEntry;
 
 use clap::ValueEnum;
 
@@ -28,6 +29,14 @@ pub enum IconSet {
     Status,
 }
 
+/// Looks `name` up in one of the standard icon tables.
+pub fn find_icon(icons: &[IconEntry], name: &str) -> Option<&'static str> {
+    icons
+        .iter()
+        .find(|(icon_name, _)| *icon_name == name)
+        .map(|(icon_name, _)| *icon_name)
+}
+
 pub fn handle_icon_listing(set: IconSet) {
     let mut lists = Vec::new();
     match set {
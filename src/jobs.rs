@@ -0,0 +1,300 @@
+use crate::bus;
+use crate::config::Config;
+use crate::notification::Notification;
+use crate::paths::state_dir;
+use chrono::{DateTime, Local};
+use clap::Subcommand;
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+use std::sync::OnceLock;
+
+#[derive(Debug, Subcommand)]
+pub enum JobsCommand {
+    /// List pending reminders
+    List,
+
+    /// Cancel pending reminders by ID
+    Cancel {
+        #[arg(required = true)]
+        ids: Vec<u32>,
+    },
+
+    /// Run the scheduler in the foreground, e.g. from an autostart entry or a systemd user
+    /// unit
+    ///
+    /// Besides delivering reminders from `in` and `at`, this fires the recurring
+    /// `[[reminder]]`s from the config, which only happens while it runs. It waits for a
+    /// scheduler started by `in` or `at` to finish first, then keeps running.
+    Run {
+        /// Exit once no jobs or deferred notifications are left, instead of firing reminders
+        #[arg(long, hide = true)]
+        until_idle: bool,
+    },
+}
+
+/// A notification scheduled with `alertify in` or `alertify at`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Job {
+    pub id: u32,
+    pub due: DateTime<Local>,
+    pub created: DateTime<Local>,
+    pub notification: Notification,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct JobStore {
+    next_id: u32,
+    pub jobs: Vec<Job>,
+}
+
+/// The `--config` and `--profile` this invocation was given, passed on to a scheduler it
+/// starts.
+static INVOCATION: OnceLock<(Option<PathBuf>, Option<String>)> = OnceLock::new();
+
+/// Records `config` and `profile` for `ensure_scheduler`. Call before scheduling anything.
+pub fn set_invocation(config: Option<PathBuf>, profile: Option<String>) {
+    let _ = INVOCATION.set((config, profile));
+}
+
+pub fn jobs_path() -> PathBuf {
+    state_dir().join("jobs.json")
+}
+
+fn scheduler_lock_path() -> PathBuf {
+    state_dir().join("scheduler.lock")
+}
+
+/// Runs `f` on the job store while holding an exclusive lock on it, saving any changes.
+pub fn with_jobs<T>(f: impl FnOnce(&mut JobStore) -> T) -> Result<T, Box<dyn Error>> {
+    fs::create_dir_all(state_dir())?;
+    let mut file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(jobs_path())?;
+    file.lock()?;
+
+    let mut source = String::new();
+    file.read_to_string(&mut source)?;
+    let mut store: JobStore = if source.trim().is_empty() {
+        JobStore::default()
+    } else {
+        serde_json::from_str(&source)
+            .map_err(|e| format!("invalid {}: {e}", jobs_path().display()))?
+    };
+
+    let result = f(&mut store);
+
+    file.set_len(0)?;
+    file.seek(SeekFrom::Start(0))?;
+    file.write_all(&serde_json::to_vec_pretty(&store)?)?;
+
+    Ok(result)
+}
+
+/// The pending jobs, read under a shared lock without saving the store again, so that
+/// watching the file doesn't mistake a look for a change.
+pub fn read_jobs() -> Result<Vec<Job>, Box<dyn Error>> {
+    let mut file = match File::open(jobs_path()) {
+        Ok(file) => file,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    file.lock_shared()?;
+
+    let mut source = String::new();
+    file.read_to_string(&mut source)?;
+    if source.trim().is_empty() {
+        return Ok(Vec::new());
+    }
+    let store: JobStore = serde_json::from_str(&source)
+        .map_err(|e| format!("invalid {}: {e}", jobs_path().display()))?;
+    Ok(store.jobs)
+}
+
+impl JobStore {
+    pub fn add(&mut self, due: DateTime<Local>, notification: Notification) -> u32 {
+        self.next_id += 1;
+        self.jobs.push(Job {
+            id: self.next_id,
+            due,
+            created: Local::now(),
+            notification,
+        });
+        self.next_id
+    }
+
+    /// Removes and returns every job due at or before `now`.
+    pub fn take_due(&mut self, now: DateTime<Local>) -> Vec<Job> {
+        let (due, pending) = std::mem::take(&mut self.jobs)
+            .into_iter()
+            .partition(|job| job.due <= now);
+        self.jobs = pending;
+        due
+    }
+
+    pub fn next_due(&self) -> Option<DateTime<Local>> {
+        self.jobs.iter().map(|job| job.due).min()
+    }
+}
+
+/// Returns true while a scheduler process holds the scheduler lock.
+pub fn scheduler_running() -> Result<bool, Box<dyn Error>> {
+    fs::create_dir_all(state_dir())?;
+    let file = File::create(scheduler_lock_path())?;
+    Ok(file.try_lock().is_err())
+}
+
+/// Takes the scheduler lock for the lifetime of the returned file, or `None` if another
+/// scheduler already holds it.
+pub fn acquire_scheduler_lock() -> Result<Option<File>, Box<dyn Error>> {
+    fs::create_dir_all(state_dir())?;
+    let file = File::create(scheduler_lock_path())?;
+    match file.try_lock() {
+        Ok(()) => Ok(Some(file)),
+        Err(_) => Ok(None),
+    }
+}
+
+/// Waits until no other scheduler holds the scheduler lock, then takes it for the lifetime
+/// of the returned file.
+pub async fn wait_for_scheduler_lock() -> Result<File, Box<dyn Error>> {
+    fs::create_dir_all(state_dir())?;
+    let file = File::create(scheduler_lock_path())?;
+    let file = tokio::task::spawn_blocking(move || file.lock().map(|()| file)).await??;
+    Ok(file)
+}
+
+/// Starts a detached scheduler for pending jobs and deferred notifications unless one is
+/// already running, with the config and profile of this invocation.
+pub fn ensure_scheduler() -> Result<(), Box<dyn Error>> {
+    if scheduler_running()? {
+        return Ok(());
+    }
+
+    let mut command = Command::new(std::env::current_exe()?);
+    if let Some((config, profile)) = INVOCATION.get() {
+        if let Some(config) = config {
+            command.arg("--config").arg(config);
+        }
+        if let Some(profile) = profile {
+            command.arg("--profile").arg(profile);
+        }
+    }
+    command
+        .args(["jobs", "run", "--until-idle"])
+        .stdin(Stdio::null())
+        .stdout(Stdio::null())
+        .stderr(Stdio::null());
+    if let Some(address) = bus::session_address() {
+        command.env("DBUS_SESSION_BUS_ADDRESS", address);
+    }
+    // Its own process group keeps it alive when the terminal that started it goes away.
+    std::os::unix::process::CommandExt::process_group(&mut command, 0);
+    command.spawn()?;
+
+    Ok(())
+}
+
+/// Starts the scheduler if a job came due while none was running, e.g. while the machine
+/// was off, so it is delivered now rather than whenever the next one is scheduled.
+pub fn resume_due_jobs() -> Result<(), Box<dyn Error>> {
+    let now = Local::now();
+    if read_jobs()?.iter().any(|job| job.due <= now) {
+        ensure_scheduler()?;
+    }
+    Ok(())
+}
+
+pub fn schedule(due: DateTime<Local>, notification: Notification) -> Result<(), Box<dyn Error>> {
+    let title = notification.title.clone();
+    let id = with_jobs(|store| store.add(due, notification))?;
+    ensure_scheduler()?;
+    println!(
+        "Scheduled #{id} \"{title}\" for {}.",
+        due.format("%Y-%m-%d %H:%M:%S")
+    );
+    Ok(())
+}
+
+fn format_remaining(due: DateTime<Local>) -> String {
+    let seconds = (due - Local::now()).num_seconds();
+    if seconds <= 0 {
+        return String::from("overdue");
+    }
+    let (hours, minutes, seconds) = (seconds / 3600, seconds % 3600 / 60, seconds % 60);
+    if hours > 0 {
+        format!("in {hours}h{minutes:02}m")
+    } else if minutes > 0 {
+        format!("in {minutes}m{seconds:02}s")
+    } else {
+        format!("in {seconds}s")
+    }
+}
+
+pub fn handle_jobs_list(config: &Config) -> Result<(), Box<dyn Error>> {
+    let mut jobs = read_jobs()?;
+    let now = Local::now();
+    if jobs.iter().any(|job| job.due <= now) {
+        ensure_scheduler()?;
+    }
+    if jobs.is_empty() && config.reminder.is_empty() {
+        println!("No pending reminders.");
+        return Ok(());
+    }
+
+    jobs.sort_by_key(|job| job.due);
+    for job in jobs {
+        println!(
+            "#{:<4} {}  ({})  {}",
+            job.id,
+            job.due.format("%Y-%m-%d %H:%M:%S"),
+            format_remaining(job.due),
+            job.notification.title
+        );
+    }
+
+    for reminder in &config.reminder {
+        match reminder.next_after(now) {
+            Some(next) => println!(
+                "every {}  ({})  {}",
+                next.format("%Y-%m-%d %H:%M:%S"),
+                format_remaining(next),
+                reminder.title
+            ),
+            None => println!("never {:19}  {}", "", reminder.title),
+        }
+    }
+    if !config.reminder.is_empty() && !scheduler_running()? {
+        eprintln!(
+            "The scheduler isn't running, so recurring reminders won't fire; start it with `alertify jobs run`."
+        );
+    }
+    Ok(())
+}
+
+pub fn handle_jobs_cancel(ids: &[u32]) -> Result<(), Box<dyn Error>> {
+    let missing = with_jobs(|store| {
+        let mut missing = Vec::new();
+        for id in ids {
+            match store.jobs.iter().position(|job| job.id == *id) {
+                Some(index) => {
+                    let job = store.jobs.remove(index);
+                    println!("Cancelled #{} \"{}\".", job.id, job.notification.title);
+                }
+                None => missing.push(id.to_string()),
+            }
+        }
+        missing
+    })?;
+
+    if !missing.is_empty() {
+        return Err(format!("no pending reminder with ID {}", missing.join(", ")).into());
+    }
+    Ok(())
+}
@@ -1,45 +1,217 @@
+use chrono::Local;
 use clap::Parser;
 use std::error::Error;
+use std::path::Path;
 
 pub mod actions;
+pub mod broadcast;
+pub mod bus;
 pub mod cli;
+pub mod clock;
+pub mod compat;
+pub mod config;
+pub mod daemon;
+pub mod deliver;
+pub mod dnd;
+pub mod duration;
+pub mod history;
 pub mod icons;
+pub mod jobs;
+pub mod monitor;
 pub mod notification;
+pub mod paths;
 pub mod pomodoro;
+pub mod recurring;
+pub mod replay;
+pub mod run;
+pub mod scheduler;
+pub mod serve;
+pub mod service;
+pub mod spec;
+pub mod spool;
+pub mod sysmon;
+pub mod tail;
+pub mod template;
+#[cfg(test)]
+mod test_support;
+pub mod throttle;
+pub mod waitpid;
+pub mod watch;
+use broadcast::handle_broadcast;
 use cli::Cli;
 use cli::Commands;
+use compat::{NotifySend, handle_compat, invoked_as_notify_send};
+use config::{Config, NotificationDefaults};
+use daemon::handle_daemon;
+use deliver::send_notification;
+use dnd::handle_dnd;
+use history::handle_history;
+use jobs::{JobsCommand, handle_jobs_cancel, handle_jobs_list, schedule};
+use monitor::handle_monitor;
+use notification::wait_for_server;
 use pomodoro::handle_pomodoro;
+use replay::handle_replay;
+use run::handle_run;
+use scheduler::handle_scheduler;
+use serve::handle_serve;
+use service::handle_service;
+use spec::handle_send;
+use spool::handle_flush;
+use sysmon::handle_monitor_system;
+use tail::handle_tail;
+use throttle::Outcome;
+use waitpid::handle_wait_pid;
+use watch::handle_watch;
 
 use icons::utils::handle_icon_listing;
-use notification::{Notification, send_notification};
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn Error>> {
+    if invoked_as_notify_send() {
+        let config = load_config_leniently(None);
+        return handle_compat(NotifySend::parse().args, &config.quiet_hours).await;
+    }
+
     let cli = Cli::parse();
+    if let Some(address) = &cli.bus_address {
+        bus::set_address(address.clone());
+    }
+    let config = if cli.command.uses_config() {
+        Config::load(cli.config.as_deref())?
+    } else if matches!(
+        cli.command,
+        Commands::Dnd { .. } | Commands::Replay(_) | Commands::Compat(_)
+    ) {
+        // Only quiet hours and the spool come from the config here, and `dnd off` has to
+        // keep working.
+        load_config_leniently(cli.config.as_deref())
+    } else {
+        Config::default()
+    };
+    let defaults = if cli.command.uses_config() {
+        config.resolve_defaults(cli.profile.as_deref())?
+    } else {
+        NotificationDefaults::default()
+    };
+
+    jobs::set_invocation(cli.config.clone(), cli.profile.clone());
+    if config.spool {
+        spool::enable();
+    }
+    if let Some(timeout) = cli.wait_for_server
+        && cli.command.sends_right_away()
+        && let Err(e) = wait_for_server(timeout).await
+    {
+        // With the spool on, the send that follows spools instead.
+        if !config.spool {
+            return Err(e);
+        }
+    }
+    if cli.command.sends_right_away() && !matches!(cli.command, Commands::Flush) {
+        spool::deliver_pending(&config.quiet_hours).await?;
+    }
 
     match cli.command {
-        Commands::Notify {
-            app_name,
-            replaces_id,
-            title,
-            body,
-            icon,
-            timeout,
-        } => {
-            let notification = Notification::new(app_name, replaces_id, title, body, icon, timeout);
-            send_notification(notification).await?;
+        Commands::Notify(args) => {
+            let notification = args.to_notification(&defaults)?;
+            let throttle = args.throttle(&config, &notification.app_name);
+            match send_notification(notification, &config.quiet_hours, &throttle).await? {
+                Outcome::Sent(id) => println!("{id}"),
+                Outcome::Duplicate { suppressed } => {
+                    eprintln!("Duplicate notification suppressed ({suppressed} so far).");
+                }
+                Outcome::Collapsed { id, suppressed } => {
+                    eprintln!("Rate limited: collapsed into #{id} ({suppressed} suppressed).");
+                }
+                Outcome::Deferred => {
+                    eprintln!("Do Not Disturb is active: notification deferred.");
+                }
+                Outcome::Spooled => {
+                    eprintln!("No notification server is reachable: notification spooled.");
+                }
+            }
+        }
+        Commands::In { delay, notify } => {
+            let due = Local::now() + chrono::Duration::from_std(delay)?;
+            schedule(due, notify.to_notification(&defaults)?)?;
+        }
+        Commands::At { time, notify } => {
+            schedule(time, notify.to_notification(&defaults)?)?;
+        }
+        Commands::Jobs { command } => match command {
+            JobsCommand::List => handle_jobs_list(&config)?,
+            JobsCommand::Cancel { ids } => handle_jobs_cancel(&ids)?,
+            JobsCommand::Run { until_idle } => {
+                handle_scheduler(&config, &defaults, until_idle).await?
+            }
+        },
+        Commands::Send { from, format } => {
+            handle_send(from, format, &defaults, &config).await?;
         }
         Commands::ListIcons { set } => {
             handle_icon_listing(set);
         }
-        Commands::Defaults { pomodoro } => {
+        Commands::Defaults { pomodoro, length } => {
             if pomodoro {
-                handle_pomodoro().await?;
+                handle_pomodoro(length, &config.quiet_hours).await?;
             } else {
                 println!("No default action specified.");
             }
         }
+        Commands::Dnd { action } => {
+            handle_dnd(action, &config.quiet_hours).await?;
+        }
+        Commands::History(args) => {
+            handle_history(args)?;
+        }
+        Commands::Replay(args) => {
+            handle_replay(args, &config.quiet_hours).await?;
+        }
+        Commands::Run(args) => {
+            let code = handle_run(args, &defaults, &config).await?;
+            std::process::exit(code);
+        }
+        Commands::WaitPid(args) => {
+            handle_wait_pid(args, &defaults, &config).await?;
+        }
+        Commands::Watch(args) => {
+            handle_watch(args, &defaults, &config).await?;
+        }
+        Commands::Tail(args) => {
+            handle_tail(args, &defaults, &config).await?;
+        }
+        Commands::MonitorSystem(args) => {
+            handle_monitor_system(args, &defaults, &config).await?;
+        }
+        Commands::Serve(args) => {
+            handle_serve(args).await?;
+        }
+        Commands::Monitor => {
+            handle_monitor().await?;
+        }
+        Commands::Broadcast(args) => {
+            handle_broadcast(args, &defaults).await?;
+        }
+        Commands::Flush => {
+            handle_flush(&config.quiet_hours).await?;
+        }
+        Commands::Daemon(args) => {
+            handle_daemon(args, &defaults, &config).await?;
+        }
+        Commands::Service => {
+            handle_service(&defaults, &config.quiet_hours).await?;
+        }
+        Commands::Compat(args) => {
+            handle_compat(args, &config.quiet_hours).await?;
+        }
     }
 
     Ok(())
 }
+
+fn load_config_leniently(path: Option<&Path>) -> Config {
+    Config::load(path).unwrap_or_else(|e| {
+        eprintln!("alertify: ignoring the config: {e}");
+        Config::default()
+    })
+}
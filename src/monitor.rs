@@ -0,0 +1,373 @@
+use crate::bus;
+use crate::history::close_reason_name;
+use chrono::{DateTime, Local, TimeDelta};
+use futures_lite::StreamExt;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+use std::error::Error;
+use zbus::fdo::MonitoringProxy;
+use zbus::message::Type;
+use zbus::zvariant::{OwnedValue, Value};
+use zbus::{MatchRule, Message, MessageStream};
+
+const INTERFACE: &str = "org.freedesktop.Notifications";
+
+/// How long a `Notify` call waits for its reply, after which it is forgotten. This is the
+/// default D-Bus method call timeout.
+const REPLY_TIMEOUT: TimeDelta = TimeDelta::seconds(25);
+
+/// The arguments of a `Notify` call.
+type NotifyCall = (
+    String,
+    u32,
+    String,
+    String,
+    String,
+    Vec<String>,
+    HashMap<String, OwnedValue>,
+    i32,
+);
+
+/// One line of `monitor` output.
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum MonitorEvent {
+    /// A `Notify` call together with its reply.
+    Notify {
+        timestamp: DateTime<Local>,
+        sender: String,
+        /// The ID the server returned, missing if it replied with an error.
+        id: Option<u32>,
+        error: Option<String>,
+        latency_ms: i64,
+        app_name: String,
+        replaces_id: u32,
+        title: String,
+        body: String,
+        icon: String,
+        timeout: i32,
+        actions: Vec<(String, String)>,
+        hints: BTreeMap<String, serde_json::Value>,
+    },
+    CloseRequested {
+        timestamp: DateTime<Local>,
+        sender: String,
+        id: u32,
+    },
+    Action {
+        timestamp: DateTime<Local>,
+        id: u32,
+        action_key: String,
+        app_name: Option<String>,
+    },
+    Closed {
+        timestamp: DateTime<Local>,
+        id: u32,
+        reason: u32,
+        reason_name: &'static str,
+        app_name: Option<String>,
+    },
+}
+
+/// Converts a hint to JSON, leaving containers such as image data as their signature.
+fn hint_to_json(value: &Value) -> serde_json::Value {
+    match value {
+        Value::U8(v) => (*v).into(),
+        Value::Bool(v) => (*v).into(),
+        Value::I16(v) => (*v).into(),
+        Value::U16(v) => (*v).into(),
+        Value::I32(v) => (*v).into(),
+        Value::U32(v) => (*v).into(),
+        Value::I64(v) => (*v).into(),
+        Value::U64(v) => (*v).into(),
+        Value::F64(v) => (*v).into(),
+        Value::Str(v) => v.as_str().into(),
+        Value::ObjectPath(v) => v.as_str().into(),
+        Value::Value(v) => hint_to_json(v),
+        other => format!("<{}>", other.value_signature()).into(),
+    }
+}
+
+/// Pairs calls with their replies and remembers which app each notification belongs to.
+#[derive(Default)]
+struct Tracker {
+    /// `Notify` calls waiting for a reply, by sender and serial.
+    pending: HashMap<(String, u32), (DateTime<Local>, NotifyCall)>,
+    app_names: HashMap<u32, String>,
+}
+
+impl Tracker {
+    fn handle(&mut self, message: &Message) -> Result<Option<MonitorEvent>, Box<dyn Error>> {
+        let header = message.header();
+        let sender = header
+            .sender()
+            .map(|sender| sender.to_string())
+            .unwrap_or_default();
+        let now = Local::now();
+        // Calls the server never answered would otherwise pile up for as long as this runs.
+        self.pending
+            .retain(|_, (called, _)| now - *called < REPLY_TIMEOUT);
+
+        match message.message_type() {
+            Type::MethodCall if header.interface().is_some_and(|i| i == INTERFACE) => {
+                match header.member().map(|member| member.as_str()) {
+                    Some("Notify") => {
+                        let call: NotifyCall = message.body().deserialize()?;
+                        let serial = header.primary().serial_num().get();
+                        self.pending.insert((sender, serial), (now, call));
+                    }
+                    Some("CloseNotification") => {
+                        let id: u32 = message.body().deserialize()?;
+                        return Ok(Some(MonitorEvent::CloseRequested {
+                            timestamp: now,
+                            sender,
+                            id,
+                        }));
+                    }
+                    _ => {}
+                }
+                Ok(None)
+            }
+            Type::MethodReturn | Type::Error => {
+                let (Some(destination), Some(serial)) =
+                    (header.destination(), header.reply_serial())
+                else {
+                    return Ok(None);
+                };
+                let Some((called, call)) = self
+                    .pending
+                    .remove(&(destination.to_string(), serial.get()))
+                else {
+                    return Ok(None);
+                };
+                let (app_name, replaces_id, icon, title, body, actions, hints, timeout) = call;
+
+                let (id, error) = if message.message_type() == Type::Error {
+                    let name = header
+                        .error_name()
+                        .map(|name| name.to_string())
+                        .unwrap_or_default();
+                    let text: String = message.body().deserialize().unwrap_or_default();
+                    (None, Some(format!("{name}: {text}")))
+                } else {
+                    let id: u32 = message.body().deserialize()?;
+                    self.app_names.insert(id, app_name.clone());
+                    (Some(id), None)
+                };
+
+                Ok(Some(MonitorEvent::Notify {
+                    timestamp: called,
+                    sender: destination.to_string(),
+                    id,
+                    error,
+                    latency_ms: (now - called).num_milliseconds(),
+                    app_name,
+                    replaces_id,
+                    title,
+                    body,
+                    icon,
+                    timeout,
+                    actions: actions
+                        .chunks(2)
+                        .map(|pair| (pair[0].clone(), pair.get(1).cloned().unwrap_or_default()))
+                        .collect(),
+                    hints: hints
+                        .iter()
+                        .map(|(key, value)| (key.clone(), hint_to_json(value)))
+                        .collect(),
+                }))
+            }
+            Type::Signal if header.interface().is_some_and(|i| i == INTERFACE) => {
+                match header.member().map(|member| member.as_str()) {
+                    Some("ActionInvoked") => {
+                        let (id, action_key): (u32, String) = message.body().deserialize()?;
+                        Ok(Some(MonitorEvent::Action {
+                            timestamp: now,
+                            id,
+                            action_key,
+                            app_name: self.app_names.get(&id).cloned(),
+                        }))
+                    }
+                    Some("NotificationClosed") => {
+                        let (id, reason): (u32, u32) = message.body().deserialize()?;
+                        Ok(Some(MonitorEvent::Closed {
+                            timestamp: now,
+                            id,
+                            reason,
+                            reason_name: close_reason_name(reason),
+                            app_name: self.app_names.remove(&id),
+                        }))
+                    }
+                    _ => Ok(None),
+                }
+            }
+            _ => Ok(None),
+        }
+    }
+}
+
+/// Watches notification traffic on the session bus and prints one JSON event per line.
+pub async fn handle_monitor() -> Result<(), Box<dyn Error>> {
+    let connection = bus::session().await?;
+    let rules = [
+        MatchRule::builder()
+            .msg_type(Type::MethodCall)
+            .interface(INTERFACE)?
+            .build(),
+        MatchRule::builder()
+            .msg_type(Type::Signal)
+            .interface(INTERFACE)?
+            .build(),
+        // Replies can't be matched by interface, so they are paired with calls here.
+        MatchRule::builder().msg_type(Type::MethodReturn).build(),
+        MatchRule::builder().msg_type(Type::Error).build(),
+    ];
+
+    let mut messages = MessageStream::from(&connection);
+    MonitoringProxy::new(&connection)
+        .await?
+        .become_monitor(&rules, 0)
+        .await
+        .map_err(|e| format!("the session bus refused to let us monitor it: {e}"))?;
+    eprintln!("Monitoring {INTERFACE}, press Ctrl-C to stop.");
+
+    let mut tracker = Tracker::default();
+    while let Some(message) = messages.next().await {
+        match tracker.handle(&message?) {
+            Ok(Some(event)) => println!("{}", serde_json::to_string(&event)?),
+            Ok(None) => {}
+            Err(e) => eprintln!("alertify: skipping malformed message: {e}"),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn notify_call(sender: &str, title: &str) -> Message {
+        let hints: HashMap<&str, Value> = HashMap::from([("urgency", Value::U8(2))]);
+        Message::method_call("/org/freedesktop/Notifications", "Notify")
+            .unwrap()
+            .interface(INTERFACE)
+            .unwrap()
+            .sender(sender)
+            .unwrap()
+            .build(&(
+                "app",
+                0u32,
+                "icon",
+                title,
+                "body",
+                vec!["default", "Open"],
+                hints,
+                -1i32,
+            ))
+            .unwrap()
+    }
+
+    fn signal(member: &str, body: &(impl Serialize + zbus::zvariant::DynamicType)) -> Message {
+        Message::signal("/org/freedesktop/Notifications", INTERFACE, member)
+            .unwrap()
+            .build(body)
+            .unwrap()
+    }
+
+    #[test]
+    fn pairs_replies_with_calls_by_sender_and_serial() {
+        let mut tracker = Tracker::default();
+        let first = notify_call(":1.5", "first");
+        let second = notify_call(":1.6", "second");
+        assert!(tracker.handle(&first).unwrap().is_none());
+        assert!(tracker.handle(&second).unwrap().is_none());
+
+        // Replies arrive out of order and are told apart by their destination.
+        let reply = Message::method_return(&second.header())
+            .unwrap()
+            .build(&(9u32,))
+            .unwrap();
+        let Some(MonitorEvent::Notify {
+            id,
+            title,
+            hints,
+            actions,
+            ..
+        }) = tracker.handle(&reply).unwrap()
+        else {
+            panic!("expected a notify event");
+        };
+        assert_eq!(id, Some(9));
+        assert_eq!(title, "second");
+        assert_eq!(hints["urgency"], 2);
+        assert_eq!(actions, [(String::from("default"), String::from("Open"))]);
+
+        let error = Message::error(&first.header(), "org.freedesktop.DBus.Error.Failed")
+            .unwrap()
+            .build(&("no thanks",))
+            .unwrap();
+        let Some(MonitorEvent::Notify { id, error, .. }) = tracker.handle(&error).unwrap() else {
+            panic!("expected a notify event");
+        };
+        assert_eq!(id, None);
+        assert_eq!(
+            error.as_deref(),
+            Some("org.freedesktop.DBus.Error.Failed: no thanks")
+        );
+
+        // A reply nobody asked for is ignored.
+        assert!(tracker.handle(&reply).unwrap().is_none());
+    }
+
+    #[test]
+    fn forgets_calls_that_were_never_answered() {
+        let mut tracker = Tracker::default();
+        let call = notify_call(":1.5", "lost");
+        tracker.handle(&call).unwrap();
+        for (called, _) in tracker.pending.values_mut() {
+            *called -= REPLY_TIMEOUT;
+        }
+
+        tracker.handle(&notify_call(":1.6", "answered")).unwrap();
+        assert_eq!(tracker.pending.len(), 1);
+        let reply = Message::method_return(&call.header())
+            .unwrap()
+            .build(&(4u32,))
+            .unwrap();
+        assert!(tracker.handle(&reply).unwrap().is_none());
+    }
+
+    #[test]
+    fn names_the_app_in_later_signals() {
+        let mut tracker = Tracker::default();
+        let call = notify_call(":1.5", "build");
+        tracker.handle(&call).unwrap();
+        let reply = Message::method_return(&call.header())
+            .unwrap()
+            .build(&(3u32,))
+            .unwrap();
+        tracker.handle(&reply).unwrap();
+
+        let Some(MonitorEvent::Action { app_name, .. }) = tracker
+            .handle(&signal("ActionInvoked", &(3u32, "default")))
+            .unwrap()
+        else {
+            panic!("expected an action event");
+        };
+        assert_eq!(app_name.as_deref(), Some("app"));
+
+        let Some(MonitorEvent::Closed {
+            reason_name,
+            app_name,
+            ..
+        }) = tracker
+            .handle(&signal("NotificationClosed", &(3u32, 2u32)))
+            .unwrap()
+        else {
+            panic!("expected a closed event");
+        };
+        assert_eq!(reason_name, "dismissed");
+        assert_eq!(app_name.as_deref(), Some("app"));
+        assert!(tracker.app_names.is_empty());
+    }
+}
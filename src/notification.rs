@@ -1,9 +1,76 @@
 use crate::actions::ACTIONS;
+use crate::bus;
+use crate::duration::format_duration;
+use crate::history::record_sent;
+use clap::ValueEnum;
+use futures_lite::StreamExt;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::error::Error;
+use std::time::Duration;
+use tokio::time::Instant;
 
+use zbus::fdo::DBusProxy;
 use zbus::{Connection, proxy, zvariant::Value};
 
+pub const DEFAULT_APP_NAME: &str = "my_app";
+pub const DEFAULT_TITLE: &str = "A summary";
+pub const DEFAULT_BODY: &str = "Some body";
+pub const DEFAULT_ICON: &str = "dialog-information";
+pub const DEFAULT_TIMEOUT: i32 = 5000;
+
+/// Well-known name a notification server owns on the session bus.
+pub const SERVER_NAME: &str = "org.freedesktop.Notifications";
+
+/// Longest pause between attempts to reach the session bus.
+const MAX_RETRY_DELAY: Duration = Duration::from_secs(2);
+
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, ValueEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Urgency {
+    Low,
+    #[default]
+    Normal,
+    Critical,
+}
+
+impl Urgency {
+    /// Byte value used for the `urgency` hint.
+    pub fn as_byte(self) -> u8 {
+        match self {
+            Urgency::Low => 0,
+            Urgency::Normal => 1,
+            Urgency::Critical => 2,
+        }
+    }
+}
+
+/// A typed hint value as described by the [hints spec](https://specifications.freedesktop.org/notification/latest/hints.html).
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HintValue {
+    Int(i32),
+    Double(f64),
+    #[serde(rename = "string")]
+    Str(String),
+    Byte(u8),
+    #[serde(rename = "boolean")]
+    Bool(bool),
+}
+
+impl HintValue {
+    fn to_value(&self) -> Value<'_> {
+        match self {
+            HintValue::Int(v) => Value::I32(*v),
+            HintValue::Double(v) => Value::F64(*v),
+            HintValue::Str(v) => Value::Str(v.as_str().into()),
+            HintValue::Byte(v) => Value::U8(*v),
+            HintValue::Bool(v) => Value::Bool(*v),
+        }
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Notification {
     pub app_name: String,
     pub replaces_id: u32,
@@ -11,6 +78,12 @@ pub struct Notification {
     pub body: String,
     pub icon: String,
     pub timeout: i32,
+    pub urgency: Urgency,
+    pub category: Option<String>,
+    pub transient: bool,
+    /// Action key and label pairs, in display order.
+    pub actions: Vec<(String, String)>,
+    pub hints: HashMap<String, HintValue>,
 }
 
 impl Notification {
@@ -22,6 +95,14 @@ impl Notification {
         icon: String,
         timeout: i32,
     ) -> Self {
+        // FIXME: this is currently not working in my sys according to the [doc](https://specifications.freedesktop.org/notification/latest/hints.html)
+        // it should play the "alarm-clock-elapsed" sound if available
+        let mut hints = HashMap::new();
+        hints.insert(
+            String::from("sound-name"),
+            HintValue::Str(String::from("message-new-instant")),
+        );
+
         Self {
             app_name,
             replaces_id,
@@ -29,6 +110,14 @@ impl Notification {
             body,
             icon,
             timeout,
+            urgency: Urgency::Normal,
+            category: None,
+            transient: false,
+            actions: ACTIONS
+                .iter()
+                .map(|(key, label)| (key.to_string(), label.to_string()))
+                .collect(),
+            hints,
         }
     }
 }
@@ -37,7 +126,7 @@ impl Notification {
     default_service = "org.freedesktop.Notifications",
     default_path = "/org/freedesktop/Notifications"
 )]
-trait Notifications {
+pub trait Notifications {
     #[allow(clippy::too_many_arguments)]
     fn notify(
         &self,
@@ -51,61 +140,124 @@ trait Notifications {
         expire_timeout: i32,
     ) -> zbus::Result<u32>;
 
+    fn close_notification(&self, id: u32) -> zbus::Result<()>;
+
     #[zbus(signal)]
-    pub async fn action_invoked(
-        ctx: &SignalContext<'_>,
-        id: u32,
-        action_key: &str,
-    ) -> zbus::Result<()>;
+    fn action_invoked(&self, id: u32, action_key: &str) -> zbus::Result<()>;
 
     #[zbus(signal)]
-    pub async fn notification_replied(
-        ctx: &SignalContext<'_>,
-        id: u32,
-        text: &str,
-    ) -> zbus::Result<()>;
+    fn notification_replied(&self, id: u32, text: &str) -> zbus::Result<()>;
 
     #[zbus(signal)]
-    pub async fn notification_closed(
-        ctx: &SignalContext<'_>,
-        id: u32,
-        reason: u32,
-    ) -> zbus::Result<()>;
+    fn notification_closed(&self, id: u32, reason: u32) -> zbus::Result<()>;
 }
 
-pub async fn send_notification(notification: Notification) -> Result<(), Box<dyn Error>> {
-    let connection = Connection::session().await?;
-    let proxy = NotificationsProxy::new(&connection).await?;
+/// Sends `notification` through an existing proxy and returns the server assigned ID.
+pub async fn notify(
+    proxy: &NotificationsProxy<'_>,
+    notification: &Notification,
+) -> zbus::Result<u32> {
+    let mut hints: HashMap<&str, Value<'_>> = notification
+        .hints
+        .iter()
+        .map(|(name, value)| (name.as_str(), value.to_value()))
+        .collect();
+    hints.insert("urgency", Value::U8(notification.urgency.as_byte()));
+    if let Some(category) = &notification.category {
+        hints.insert("category", Value::Str(category.as_str().into()));
+    }
+    if notification.transient {
+        hints.insert("transient", Value::Bool(true));
+    }
 
-    // FIXME: this is currently not working in my sys according to the [doc](https://specifications.freedesktop.org/notification/latest/hints.html)
-    // it should play the "alarm-clock-elapsed" sound if available
-    let value = Value::Str("message-new-instant".into());
-    let mut hint = HashMap::new();
-    hint.insert("sound-name", value);
+    let actions: Vec<&str> = notification
+        .actions
+        .iter()
+        .flat_map(|(key, label)| [key.as_str(), label.as_str()])
+        .collect();
 
-    let reply = proxy
+    let id = proxy
         .notify(
             &notification.app_name,
             notification.replaces_id,
             &notification.icon,
             &notification.title,
             &notification.body,
-            &[
-                ACTIONS[0].0,
-                ACTIONS[0].1,
-                ACTIONS[1].0,
-                ACTIONS[1].1,
-                ACTIONS[2].0,
-                ACTIONS[2].1,
-                ACTIONS[3].0,
-                ACTIONS[3].1,
-            ],
-            hint,
+            &actions,
+            hints,
             notification.timeout,
         )
         .await?;
 
-    dbg!(reply);
+    record_sent(notification, id);
+
+    Ok(id)
+}
+
+pub async fn send_notification(notification: Notification) -> Result<u32, Box<dyn Error>> {
+    let connection = bus::session().await?;
+    let proxy = NotificationsProxy::new(&connection).await?;
+
+    let reply = notify(&proxy, &notification).await?;
+
+    Ok(reply)
+}
 
-    Ok(())
+/// Connects to the session bus, retrying with backoff until `deadline` while it isn't up.
+async fn connect_before(deadline: Instant) -> Result<Connection, Box<dyn Error>> {
+    let mut delay = Duration::from_millis(100);
+    loop {
+        match bus::session().await {
+            Ok(connection) => return Ok(connection),
+            Err(e) if Instant::now() >= deadline => {
+                return Err(format!("could not connect to the session bus: {e}").into());
+            }
+            Err(_) => {
+                tokio::time::sleep_until(deadline.min(Instant::now() + delay)).await;
+                delay = (delay * 2).min(MAX_RETRY_DELAY);
+            }
+        }
+    }
+}
+
+/// Waits until a notification server owns its name on the session bus, or could be
+/// started for it by D-Bus activation.
+pub async fn wait_for_server(timeout: Duration) -> Result<(), Box<dyn Error>> {
+    let deadline = Instant::now() + timeout;
+    let connection = connect_before(deadline).await?;
+    let dbus = DBusProxy::new(&connection).await?;
+
+    // Subscribe before checking so a server that appears in between isn't missed.
+    let mut changes = dbus
+        .receive_name_owner_changed_with_args(&[(0, SERVER_NAME)])
+        .await?;
+    let name = SERVER_NAME.try_into()?;
+    if dbus.name_has_owner(name).await?
+        || dbus
+            .list_activatable_names()
+            .await?
+            .iter()
+            .any(|name| name.as_str() == SERVER_NAME)
+    {
+        return Ok(());
+    }
+
+    let appeared = async {
+        while let Some(change) = changes.next().await {
+            if change.args()?.new_owner().is_some() {
+                return Ok(true);
+            }
+        }
+        Ok::<bool, zbus::Error>(false)
+    };
+    match tokio::time::timeout_at(deadline, appeared).await {
+        Ok(Ok(true)) => Ok(()),
+        Ok(Ok(false)) => Err("the session bus closed while waiting for a notification server".into()),
+        Ok(Err(e)) => Err(e.into()),
+        Err(_) => Err(format!(
+            "no notification server appeared on the session bus within {}, is a notification daemon such as dunst or mako running?",
+            format_duration(timeout)
+        )
+        .into()),
+    }
 }
@@ -0,0 +1,33 @@
+use std::env;
+use std::path::PathBuf;
+
+fn home_dir() -> PathBuf {
+    env::var_os("HOME")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/"))
+}
+
+fn xdg_dir(var: &str, fallback: &str) -> PathBuf {
+    env::var_os(var)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(|| home_dir().join(fallback))
+}
+
+/// `$XDG_CONFIG_HOME/alertify`, defaulting to `~/.config/alertify`.
+pub fn config_dir() -> PathBuf {
+    xdg_dir("XDG_CONFIG_HOME", ".config").join("alertify")
+}
+
+/// `$XDG_STATE_HOME/alertify`, defaulting to `~/.local/state/alertify`.
+pub fn state_dir() -> PathBuf {
+    xdg_dir("XDG_STATE_HOME", ".local/state").join("alertify")
+}
+
+/// `$XDG_RUNTIME_DIR`, or the state directory where there is none.
+pub fn runtime_dir() -> PathBuf {
+    env::var_os("XDG_RUNTIME_DIR")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .unwrap_or_else(state_dir)
+}
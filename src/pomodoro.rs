@@ -9,10 +9,31 @@ use std::sync::Arc;
 use std::sync::RwLock;
 use std::thread;
 
-use crate::notification::{Notification, send_notification};
+use crate::clock::TimeWindow;
+use crate::deliver::send_notification;
+use crate::dnd::{hold, release};
+use crate::duration::parse_duration;
+use crate::notification::Notification;
+use crate::throttle::Throttle;
 use std::error::Error;
 use std::time::Duration;
 
+/// Length of a work block when none is given.
+const DEFAULT_LENGTH: Duration = Duration::from_secs(25 * 60);
+
+/// Parses the length of a work block, where `default` means 25 minutes.
+pub fn parse_pomodoro_length(s: &str) -> Result<Duration, String> {
+    let length = match s.trim() {
+        "default" => DEFAULT_LENGTH,
+        "never" => return Err(String::from("a pomodoro needs a length, e.g. 25m")),
+        text => parse_duration(text)?,
+    };
+    if length < Duration::from_secs(1) {
+        return Err(format!("pomodoro length `{s}` must be at least 1s"));
+    }
+    Ok(length)
+}
+
 struct RawModeGuard;
 impl RawModeGuard {
     fn new() -> Result<Self, std::io::Error> {
@@ -27,6 +48,26 @@ impl Drop for RawModeGuard {
     }
 }
 
+/// Keeps do-not-disturb on during a work block and turns it off afterwards, unless it was
+/// already on. Should the process be killed instead, the setting lapses with it.
+struct FocusGuard {
+    held: bool,
+}
+
+impl FocusGuard {
+    fn new() -> Result<Self, Box<dyn Error>> {
+        Ok(Self { held: hold()? })
+    }
+}
+
+impl Drop for FocusGuard {
+    fn drop(&mut self) {
+        if self.held {
+            let _ = release();
+        }
+    }
+}
+
 #[derive(Clone, Debug)]
 pub enum PomodoroState {
     Work,
@@ -76,8 +117,11 @@ pub fn handle_state(state: Arc<RwLock<PomodoroState>>) -> Result<(), Box<dyn Err
     }
 }
 
-pub async fn handle_pomodoro() -> Result<(), Box<dyn Error>> {
-    let total_seconds = 25 * 60;
+pub async fn handle_pomodoro(
+    length: Duration,
+    quiet_hours: &[TimeWindow],
+) -> Result<(), Box<dyn Error>> {
+    let total_seconds = length.as_secs();
 
     let state = Arc::new(RwLock::new(PomodoroState::Work));
     let mut remaining_time = Duration::from_secs(total_seconds);
@@ -99,9 +143,12 @@ pub async fn handle_pomodoro() -> Result<(), Box<dyn Error>> {
     );
     progress_bar.set_prefix("Pomodoro");
 
+    let focus_guard = FocusGuard::new()?;
+
     let clone_state = Arc::clone(&state);
     thread::spawn(move || {
-        handle_state(clone_state).unwrap();
+        // Without a terminal there are no keys to read, so the timer just runs.
+        let _ = handle_state(clone_state);
     });
 
     loop {
@@ -124,7 +171,9 @@ pub async fn handle_pomodoro() -> Result<(), Box<dyn Error>> {
                 if remaining_time.as_secs() == 0 {
                     progress_bar.finish_with_message("Done! Sending notification...");
                     let _ = disable_raw_mode();
-                    return send_notification(notification).await;
+                    drop(focus_guard);
+                    send_notification(notification, quiet_hours, &Throttle::default()).await?;
+                    return Ok(());
                 }
             }
             PomodoroState::Pause => {
@@ -0,0 +1,477 @@
+use crate::clock::{TimeWindow, parse_time_of_day};
+use crate::config::NotificationDefaults;
+use crate::duration::parse_duration;
+use crate::notification::{Notification, Urgency};
+use chrono::{
+    DateTime, Datelike, Duration as ChronoDuration, Local, NaiveDate, NaiveTime, TimeZone, Timelike,
+};
+use serde::{Deserialize, Deserializer};
+use std::time::Duration;
+
+/// How far ahead to look for the next occurrence before giving up, e.g. for `0 0 31 2 *`.
+const SEARCH_DAYS: i64 = 5 * 366;
+
+/// A reminder from the config file that `alertify jobs run` fires on a schedule.
+///
+/// ```toml
+/// [[reminder]]
+/// title = "Stand-up"
+/// schedule = "every weekday at 09:55"
+///
+/// [[reminder]]
+/// title = "Drink some water"
+/// schedule = "every 45m"
+/// working_hours = { start = "09:00", end = "17:30", days = ["mon", "tue", "wed", "thu", "fri"] }
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Reminder {
+    pub schedule: Schedule,
+    /// Only fire while inside this window.
+    pub working_hours: Option<TimeWindow>,
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    pub app_name: Option<String>,
+    pub icon: Option<String>,
+    #[serde(default, deserialize_with = "crate::duration::deserialize_timeout")]
+    pub timeout: Option<i32>,
+    pub urgency: Option<Urgency>,
+}
+
+impl Reminder {
+    pub fn to_notification(&self, defaults: &NotificationDefaults) -> Notification {
+        NotificationDefaults {
+            app_name: self.app_name.clone(),
+            icon: self.icon.clone(),
+            timeout: self.timeout,
+            urgency: self.urgency,
+        }
+        .or(defaults.clone())
+        .to_notification(self.title.clone(), self.body.clone())
+    }
+
+    /// The first time after `after` that the schedule fires inside the working hours.
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let anchor = self.working_hours.as_ref().map(|window| window.start);
+        let mut time = after;
+        // Bounded, since a window may never overlap the schedule.
+        for _ in 0..10_000 {
+            time = self.schedule.next_after(time, anchor)?;
+            if self
+                .working_hours
+                .as_ref()
+                .is_none_or(|window| window.contains(time))
+            {
+                return Some(time);
+            }
+        }
+        None
+    }
+}
+
+/// When a reminder fires: `every DURATION`, `every DAYS at HH:MM`, or a five-field cron
+/// expression.
+#[derive(Clone, Debug)]
+pub enum Schedule {
+    Every(Duration),
+    Cron(Cron),
+}
+
+impl Schedule {
+    /// Intervals start counting at midnight, or at `anchor` when given, so `every 1h`
+    /// fires on the hour. Intervals of a day or more count from 1970-01-01 at that time.
+    pub fn next_after(
+        &self,
+        after: DateTime<Local>,
+        anchor: Option<NaiveTime>,
+    ) -> Option<DateTime<Local>> {
+        match self {
+            Schedule::Every(interval) => next_interval(*interval, after, anchor),
+            Schedule::Cron(cron) => cron.next_after(after),
+        }
+    }
+}
+
+fn at_local(date: NaiveDate, time: NaiveTime) -> Option<DateTime<Local>> {
+    Local.from_local_datetime(&date.and_time(time)).earliest()
+}
+
+fn next_interval(
+    interval: Duration,
+    after: DateTime<Local>,
+    anchor: Option<NaiveTime>,
+) -> Option<DateTime<Local>> {
+    let step = ChronoDuration::from_std(interval).ok()?;
+
+    let anchor = anchor.unwrap_or(NaiveTime::MIN);
+    // Intervals of a day or more can't restart every day, so count from a fixed local date
+    // instead, in whole days where they allow so the time of day survives DST changes.
+    if step >= ChronoDuration::days(1) {
+        let origin = NaiveDate::from_ymd_opt(1970, 1, 1)?;
+        let days = step.num_days();
+        if step == ChronoDuration::days(days) {
+            let elapsed = (after.date_naive() - origin).num_days();
+            let date = origin + ChronoDuration::days(elapsed - elapsed.rem_euclid(days));
+            let candidate = at_local(date, anchor)?;
+            if candidate > after {
+                return Some(candidate);
+            }
+            return at_local(date + ChronoDuration::days(days), anchor);
+        }
+        let start = at_local(origin, anchor)?;
+        let steps = (after - start).num_seconds().div_euclid(step.num_seconds()) + 1;
+        return Some(start + step * i32::try_from(steps).ok()?);
+    }
+
+    let mut date = after.date_naive();
+    if after.time() < anchor {
+        date = date.pred_opt()?;
+    }
+    let start = at_local(date, anchor)?;
+    let next_start = at_local(date.succ_opt()?, anchor)?;
+
+    let steps = (after - start).num_seconds() / step.num_seconds() + 1;
+    let candidate = start + step * i32::try_from(steps).ok()?;
+    Some(candidate.min(next_start))
+}
+
+impl std::str::FromStr for Schedule {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Schedule, String> {
+        let text = s.trim();
+        let Some(rest) = text.strip_prefix("every ") else {
+            return Cron::parse(text).map(Schedule::Cron);
+        };
+
+        let rest = rest.trim();
+        let Some((days, time)) = rest.split_once(" at ") else {
+            let interval = parse_duration(rest).map_err(|_| {
+                format!("invalid schedule `{s}`, expected e.g. `every 45m` or `every day at 09:00`")
+            })?;
+            if interval < Duration::from_secs(1) {
+                return Err(format!("interval in schedule `{s}` must be at least 1s"));
+            }
+            return Ok(Schedule::Every(interval));
+        };
+
+        let time = parse_time_of_day(time)?;
+        let weekdays = match days.trim() {
+            "day" => "*",
+            "weekday" => "1-5",
+            "weekend" => "0,6",
+            days => days,
+        };
+        let cron = Cron::parse(&format!("{} {} * * {weekdays}", time.minute(), time.hour()))
+            .map_err(|_| format!("invalid days `{days}` in schedule `{s}`"))?;
+        Ok(Schedule::Cron(cron))
+    }
+}
+
+impl<'de> Deserialize<'de> for Schedule {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Schedule, D::Error> {
+        String::deserialize(deserializer)?
+            .parse()
+            .map_err(serde::de::Error::custom)
+    }
+}
+
+/// A classic `minute hour day-of-month month day-of-week` expression.
+///
+/// Fields take `*`, numbers, ranges, lists and `/step`; days of the week may also be
+/// written `mon` to `sun`. As in cron, when both day fields are restricted a day matching
+/// either one fires.
+#[derive(Clone, Debug)]
+pub struct Cron {
+    minutes: Vec<u32>,
+    hours: Vec<u32>,
+    days: Vec<u32>,
+    months: Vec<u32>,
+    weekdays: Vec<u32>,
+    any_day: bool,
+    any_weekday: bool,
+}
+
+const WEEKDAY_NAMES: [&str; 7] = ["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+const MONTH_NAMES: [&str; 12] = [
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+fn parse_value(value: &str, min: u32, names: &[&str]) -> Option<u32> {
+    let value = value.to_ascii_lowercase();
+    names
+        .iter()
+        .position(|name| value == *name)
+        .map(|index| index as u32 + min)
+        .or_else(|| value.parse().ok())
+}
+
+/// Expands one cron field into the sorted values it matches.
+fn parse_field(field: &str, min: u32, max: u32, names: &[&str]) -> Result<Vec<u32>, String> {
+    let invalid = || format!("invalid cron field `{field}`");
+    let mut values = Vec::new();
+
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().map_err(|_| invalid())?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            return Err(invalid());
+        }
+
+        let (start, end) = match range {
+            "*" => (min, max),
+            range => match range.split_once('-') {
+                Some((start, end)) => (
+                    parse_value(start, min, names).ok_or_else(invalid)?,
+                    parse_value(end, min, names).ok_or_else(invalid)?,
+                ),
+                None => {
+                    let value = parse_value(range, min, names).ok_or_else(invalid)?;
+                    // `5/15` means from 5 to the end in steps of 15.
+                    (value, if part.contains('/') { max } else { value })
+                }
+            },
+        };
+        if start < min || end > max || start > end {
+            return Err(format!(
+                "cron field `{field}` is out of range, expected {min}-{max}"
+            ));
+        }
+        values.extend((start..=end).step_by(step as usize));
+    }
+
+    values.sort_unstable();
+    values.dedup();
+    Ok(values)
+}
+
+impl Cron {
+    pub fn parse(s: &str) -> Result<Cron, String> {
+        let fields: Vec<&str> = s.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            return Err(format!(
+                "invalid schedule `{s}`, expected a cron expression with five fields"
+            ));
+        };
+
+        // Both 0 and 7 mean Sunday.
+        let mut weekdays: Vec<u32> = parse_field(weekday, 0, 7, &WEEKDAY_NAMES)?
+            .into_iter()
+            .map(|day| day % 7)
+            .collect();
+        weekdays.sort_unstable();
+        weekdays.dedup();
+
+        Ok(Cron {
+            minutes: parse_field(minute, 0, 59, &[])?,
+            hours: parse_field(hour, 0, 23, &[])?,
+            days: parse_field(day, 1, 31, &[])?,
+            months: parse_field(month, 1, 12, &MONTH_NAMES)?,
+            weekdays,
+            any_day: day == "*",
+            any_weekday: weekday == "*",
+        })
+    }
+
+    fn matches_date(&self, date: chrono::NaiveDate) -> bool {
+        if !self.months.contains(&date.month()) {
+            return false;
+        }
+        let day = self.days.contains(&date.day());
+        let weekday = self
+            .weekdays
+            .contains(&date.weekday().num_days_from_sunday());
+        match (self.any_day, self.any_weekday) {
+            (true, true) => true,
+            (true, false) => weekday,
+            (false, true) => day,
+            (false, false) => day || weekday,
+        }
+    }
+
+    pub fn next_after(&self, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        let first = after.date_naive();
+        (0..SEARCH_DAYS)
+            .filter_map(|offset| first.checked_add_signed(ChronoDuration::days(offset)))
+            .filter(|date| self.matches_date(*date))
+            .find_map(|date| {
+                self.hours.iter().find_map(|hour| {
+                    self.minutes.iter().find_map(|minute| {
+                        let time = NaiveTime::from_hms_opt(*hour, *minute, 0)?;
+                        at_local(date, time).filter(|candidate| *candidate > after)
+                    })
+                })
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// 2026-01-05 is a Monday.
+    fn at(day: u32, hour: u32, minute: u32) -> DateTime<Local> {
+        Local
+            .with_ymd_and_hms(2026, 1, day, hour, minute, 0)
+            .unwrap()
+    }
+
+    fn next(schedule: &str, after: DateTime<Local>) -> Option<DateTime<Local>> {
+        schedule
+            .parse::<Schedule>()
+            .unwrap()
+            .next_after(after, None)
+    }
+
+    #[test]
+    fn cron_fields_expand_ranges_lists_and_steps() {
+        let cron = Cron::parse("*/15 9-11,14 1 jan-mar mon").unwrap();
+        assert_eq!(cron.minutes, [0, 15, 30, 45]);
+        assert_eq!(cron.hours, [9, 10, 11, 14]);
+        assert_eq!(cron.days, [1]);
+        assert_eq!(cron.months, [1, 2, 3]);
+        assert_eq!(cron.weekdays, [1]);
+
+        assert_eq!(parse_field("5/20", 0, 59, &[]), Ok(vec![5, 25, 45]));
+        assert_eq!(parse_field("10-20/5", 0, 59, &[]), Ok(vec![10, 15, 20]));
+        assert_eq!(parse_field("3,1,3", 0, 59, &[]), Ok(vec![1, 3]));
+    }
+
+    #[test]
+    fn sunday_is_both_0_and_7() {
+        assert_eq!(Cron::parse("0 0 * * 7").unwrap().weekdays, [0]);
+        assert_eq!(Cron::parse("0 0 * * 5-7").unwrap().weekdays, [0, 5, 6]);
+        assert_eq!(Cron::parse("0 0 * * SUN").unwrap().weekdays, [0]);
+    }
+
+    #[test]
+    fn cron_rejects_bad_input() {
+        assert!(Cron::parse("* * * *").unwrap_err().contains("five fields"));
+        assert!(Cron::parse("* * * * * *").is_err());
+        assert!(
+            Cron::parse("60 * * * *")
+                .unwrap_err()
+                .contains("out of range")
+        );
+        assert!(Cron::parse("* 24 * * *").is_err());
+        assert!(Cron::parse("* * 0 * *").is_err());
+        assert!(Cron::parse("* * * 13 *").is_err());
+        assert!(Cron::parse("* * * * 8").is_err());
+        assert!(Cron::parse("*/0 * * * *").is_err());
+        assert!(Cron::parse("5-1 * * * *").is_err());
+        assert!(Cron::parse("a * * * *").is_err());
+        assert!(Cron::parse("* * * * funday").is_err());
+    }
+
+    #[test]
+    fn cron_finds_the_next_minute_strictly_after() {
+        assert_eq!(next("30 9 * * *", at(5, 9, 0)), Some(at(5, 9, 30)));
+        assert_eq!(next("30 9 * * *", at(5, 9, 30)), Some(at(6, 9, 30)));
+        assert_eq!(next("*/20 * * * *", at(5, 9, 41)), Some(at(5, 10, 0)));
+    }
+
+    #[test]
+    fn restricted_day_fields_match_either_one() {
+        // Only the weekday restricted: the next Friday.
+        assert_eq!(next("0 8 * * fri", at(5, 12, 0)), Some(at(9, 8, 0)));
+        // Only the day of month restricted.
+        assert_eq!(next("0 8 20 * *", at(5, 12, 0)), Some(at(20, 8, 0)));
+        // Both: the 20th or a Friday, whichever comes first.
+        assert_eq!(next("0 8 20 * fri", at(5, 12, 0)), Some(at(9, 8, 0)));
+        assert_eq!(next("0 8 7 * fri", at(5, 12, 0)), Some(at(7, 8, 0)));
+    }
+
+    #[test]
+    fn impossible_dates_never_fire() {
+        assert_eq!(next("0 0 31 2 *", at(5, 0, 0)), None);
+    }
+
+    #[test]
+    fn every_days_at_time_becomes_cron() {
+        // Monday noon: the next weekday is Tuesday, the next weekend day Saturday.
+        assert_eq!(next("every day at 09:55", at(5, 12, 0)), Some(at(6, 9, 55)));
+        assert_eq!(
+            next("every weekday at 9:55", at(9, 12, 0)),
+            Some(at(12, 9, 55))
+        );
+        assert_eq!(
+            next("every weekend at 10:00", at(5, 12, 0)),
+            Some(at(10, 10, 0))
+        );
+        assert_eq!(
+            next("every mon,wed at 07:30", at(5, 12, 0)),
+            Some(at(7, 7, 30))
+        );
+    }
+
+    #[test]
+    fn every_interval_counts_from_midnight_or_the_anchor() {
+        assert_eq!(next("every 45m", at(5, 0, 50)), Some(at(5, 1, 30)));
+        assert_eq!(next("every 1h", at(5, 9, 0)), Some(at(5, 10, 0)));
+        // The last step of a day is cut short at the next midnight.
+        assert_eq!(next("every 7h", at(5, 22, 0)), Some(at(6, 0, 0)));
+
+        let anchor = NaiveTime::from_hms_opt(9, 30, 0);
+        let schedule: Schedule = "every 2h".parse().unwrap();
+        assert_eq!(
+            schedule.next_after(at(5, 10, 0), anchor),
+            Some(at(5, 11, 30))
+        );
+        assert_eq!(schedule.next_after(at(5, 8, 0), anchor), Some(at(5, 9, 30)));
+    }
+
+    #[test]
+    fn intervals_of_days_keep_the_time_of_day() {
+        // 2026-01-05 is 20458 days after 1970-01-01, so an even day.
+        assert_eq!(next("every 2d", at(5, 12, 0)), Some(at(7, 0, 0)));
+        assert_eq!(next("every 2d", at(6, 12, 0)), Some(at(7, 0, 0)));
+
+        let anchor = NaiveTime::from_hms_opt(9, 0, 0);
+        let schedule: Schedule = "every 1d".parse().unwrap();
+        assert_eq!(schedule.next_after(at(5, 8, 0), anchor), Some(at(5, 9, 0)));
+        assert_eq!(schedule.next_after(at(5, 9, 0), anchor), Some(at(6, 9, 0)));
+    }
+
+    #[test]
+    fn schedules_reject_bad_input() {
+        assert!("every".parse::<Schedule>().is_err());
+        assert!(
+            "every soon"
+                .parse::<Schedule>()
+                .unwrap_err()
+                .contains("every 45m")
+        );
+        assert!(
+            "every 500ms"
+                .parse::<Schedule>()
+                .unwrap_err()
+                .contains("at least 1s")
+        );
+        assert!("every day at 25:00".parse::<Schedule>().is_err());
+        assert!(
+            "every someday at 09:00"
+                .parse::<Schedule>()
+                .unwrap_err()
+                .contains("invalid days `someday`")
+        );
+        assert!("tomorrow".parse::<Schedule>().is_err());
+    }
+
+    #[test]
+    fn working_hours_skip_occurrences_outside_them() {
+        let reminder: Reminder = toml::from_str(
+            r#"
+            title = "Water"
+            schedule = "every 1h"
+            working_hours = { start = "09:00", end = "17:00", days = ["mon", "tue", "wed", "thu", "fri"] }
+            "#,
+        )
+        .unwrap();
+        assert_eq!(reminder.next_after(at(5, 12, 0)), Some(at(5, 13, 0)));
+        // Friday evening moves on to the start of Monday's window.
+        assert_eq!(reminder.next_after(at(9, 17, 30)), Some(at(12, 9, 0)));
+    }
+}
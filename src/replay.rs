@@ -0,0 +1,154 @@
+use crate::clock::TimeWindow;
+use crate::deliver::Sender;
+use crate::duration::parse_timeout;
+use crate::history::HistoryRecord;
+use crate::notification::{Notification, Urgency};
+use crate::throttle::{Outcome, Throttle};
+use chrono::{DateTime, Local};
+use clap::Args;
+use std::collections::HashMap;
+use std::error::Error;
+use std::path::PathBuf;
+use std::time::Duration;
+
+#[derive(Args, Debug)]
+pub struct ReplayArgs {
+    /// JSONL recording, in the format written to `history.jsonl`
+    pub file: PathBuf,
+
+    /// Keep the original spacing in time between notifications
+    #[arg(short, long, default_value_t = false)]
+    pub keep_timing: bool,
+
+    /// Divide the original spacing by this factor when keeping timing
+    #[arg(long, default_value_t = 1.0, requires = "keep_timing")]
+    pub speed: f64,
+
+    /// Override the application name
+    #[arg(short, long)]
+    pub app_name: Option<String>,
+
+    /// Override the title
+    #[arg(short, long)]
+    pub title: Option<String>,
+
+    /// Override the body text
+    #[arg(short, long)]
+    pub body: Option<String>,
+
+    /// Override the icon name
+    #[arg(short, long)]
+    pub icon: Option<String>,
+
+    /// Override the timeout, e.g. 10s, `never` or `default`
+    #[arg(short = 's', long, value_parser = parse_timeout, allow_negative_numbers = true)]
+    pub timeout: Option<i32>,
+
+    /// Override the urgency
+    #[arg(short, long, value_enum)]
+    pub urgency: Option<Urgency>,
+}
+
+impl ReplayArgs {
+    fn apply_overrides(&self, notification: &mut Notification) {
+        if let Some(app_name) = &self.app_name {
+            notification.app_name = app_name.clone();
+        }
+        if let Some(title) = &self.title {
+            notification.title = title.clone();
+        }
+        if let Some(body) = &self.body {
+            notification.body = body.clone();
+        }
+        if let Some(icon) = &self.icon {
+            notification.icon = icon.clone();
+        }
+        if let Some(timeout) = self.timeout {
+            notification.timeout = timeout;
+        }
+        if let Some(urgency) = self.urgency {
+            notification.urgency = urgency;
+        }
+    }
+}
+
+/// Reads the `sent` records of a recording; action and close records are skipped.
+fn load_recording(args: &ReplayArgs) -> Result<Vec<HistoryRecord>, Box<dyn Error>> {
+    let source = std::fs::read_to_string(&args.file)
+        .map_err(|e| format!("failed to read {}: {e}", args.file.display()))?;
+
+    let mut records = Vec::new();
+    for (index, line) in source.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let record: HistoryRecord = serde_json::from_str(line)
+            .map_err(|e| format!("{}:{}: {e}", args.file.display(), index + 1))?;
+        if matches!(record, HistoryRecord::Sent { .. }) {
+            records.push(record);
+        }
+    }
+
+    Ok(records)
+}
+
+pub async fn handle_replay(
+    args: ReplayArgs,
+    quiet_hours: &[TimeWindow],
+) -> Result<(), Box<dyn Error>> {
+    if !(args.speed.is_finite() && args.speed > 0.0) {
+        return Err("--speed must be a number greater than zero".into());
+    }
+
+    let records = load_recording(&args)?;
+
+    let mut sender = Sender::default();
+
+    // Recorded IDs belong to the original server, so replacements are pointed at the replayed IDs.
+    let mut replayed_ids: HashMap<u32, u32> = HashMap::new();
+    let mut previous: Option<DateTime<Local>> = None;
+    for record in records {
+        let HistoryRecord::Sent {
+            timestamp,
+            id,
+            mut notification,
+        } = record
+        else {
+            continue;
+        };
+
+        if args.keep_timing
+            && let Some(previous) = previous
+            && let Ok(gap) = (timestamp - previous).to_std()
+        {
+            let gap = Duration::try_from_secs_f64(gap.as_secs_f64() / args.speed)
+                .map_err(|e| format!("--speed is too slow to wait out a gap of {gap:?}: {e}"))?;
+            tokio::time::sleep(gap).await;
+        }
+        previous = Some(timestamp);
+
+        notification.replaces_id = replayed_ids
+            .get(&notification.replaces_id)
+            .copied()
+            .unwrap_or(0);
+        args.apply_overrides(&mut notification);
+
+        match sender
+            .send(&notification, quiet_hours, &Throttle::default())
+            .await?
+        {
+            Outcome::Sent(new_id) => {
+                println!("#{id} -> #{new_id}: {}", notification.title);
+                replayed_ids.insert(id, new_id);
+            }
+            Outcome::Deferred => println!("#{id} deferred: {}", notification.title),
+            Outcome::Spooled => println!(
+                "#{id} spooled (no notification server): {}",
+                notification.title
+            ),
+            outcome => unreachable!("{outcome:?} without a throttle"),
+        }
+    }
+
+    Ok(())
+}
@@ -0,0 +1,247 @@
+use crate::config::{Config, NotificationDefaults};
+use crate::deliver::send_notification;
+use crate::duration::{format_duration, parse_duration};
+use crate::notification::Urgency;
+use crate::throttle::Throttle;
+use clap::Args;
+use std::collections::VecDeque;
+use std::error::Error;
+use std::io::{ErrorKind, Write};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{ExitStatus, Stdio};
+use std::time::{Duration, Instant};
+use tokio::io::AsyncReadExt;
+use tokio::process::Command;
+use tokio::signal::unix::{SignalKind, signal};
+
+/// Longest command line shown in the notification title.
+const TITLE_COMMAND_LEN: usize = 48;
+
+/// Longest stderr line kept for the notification body.
+const TAIL_LINE_LEN: usize = 200;
+
+/// How long stderr is still read once the command has exited.
+const DRAIN_TIMEOUT: Duration = Duration::from_millis(200);
+
+#[derive(Args, Debug)]
+pub struct RunArgs {
+    /// Only notify if the command ran at least this long, e.g. 30s
+    #[arg(short, long, value_parser = parse_duration)]
+    pub min_duration: Option<Duration>,
+
+    /// Lines of stderr to include in the notification
+    #[arg(short = 'n', long, default_value_t = 5)]
+    pub lines: usize,
+
+    /// Notification title instead of the command line
+    #[arg(short, long)]
+    pub title: Option<String>,
+
+    /// Icon instead of `emblem-default` or `dialog-error`
+    #[arg(short, long)]
+    pub icon: Option<String>,
+
+    /// The command to run and its arguments
+    #[arg(required = true, trailing_var_arg = true, allow_hyphen_values = true)]
+    pub command: Vec<String>,
+}
+
+/// The last few lines written to stderr, with carriage-return progress bars collapsed to
+/// their final state.
+struct Tail {
+    lines: VecDeque<String>,
+    partial: Vec<u8>,
+    keep: usize,
+}
+
+impl Tail {
+    fn new(keep: usize) -> Tail {
+        Tail {
+            lines: VecDeque::with_capacity(keep),
+            partial: Vec::new(),
+            keep,
+        }
+    }
+
+    fn push_line(&mut self, bytes: &[u8]) {
+        let text = String::from_utf8_lossy(bytes);
+        let text = text.rsplit('\r').next().unwrap_or_default().trim_end();
+        if text.is_empty() || self.keep == 0 {
+            return;
+        }
+        if self.lines.len() == self.keep {
+            self.lines.pop_front();
+        }
+        self.lines
+            .push_back(text.chars().take(TAIL_LINE_LEN).collect());
+    }
+
+    fn extend(&mut self, chunk: &[u8]) {
+        for byte in chunk {
+            if *byte == b'\n' {
+                let line = std::mem::take(&mut self.partial);
+                self.push_line(&line);
+            } else {
+                self.partial.push(*byte);
+            }
+        }
+    }
+
+    fn finish(mut self) -> Vec<String> {
+        let line = std::mem::take(&mut self.partial);
+        self.push_line(&line);
+        self.lines.into()
+    }
+}
+
+/// Copies a chunk of the child's stderr to ours and keeps it for the tail.
+fn pass_through(tail: &mut Tail, chunk: &[u8]) -> std::io::Result<()> {
+    let mut out = std::io::stderr().lock();
+    out.write_all(chunk)?;
+    out.flush()?;
+    tail.extend(chunk);
+    Ok(())
+}
+
+/// Describes how a process ended, with the exit code a shell would report for it.
+pub fn describe_status(status: ExitStatus) -> (String, i32) {
+    match (status.code(), status.signal()) {
+        (Some(code), _) => (format!("exit status {code}"), code),
+        (None, Some(signal)) => (format!("killed by signal {signal}"), 128 + signal),
+        (None, None) => (String::from("unknown exit status"), 1),
+    }
+}
+
+fn shorten(command: &str) -> String {
+    if command.chars().count() <= TITLE_COMMAND_LEN {
+        return command.to_string();
+    }
+    let mut short: String = command.chars().take(TITLE_COMMAND_LEN - 1).collect();
+    short.push('…');
+    short
+}
+
+/// Sends the notification for a finished command or process. Failures are critical, so
+/// they get through do-not-disturb; `success` is `None` when the outcome is unknown.
+pub async fn notify_finished(
+    title: String,
+    body: String,
+    success: Option<bool>,
+    icon: Option<String>,
+    defaults: &NotificationDefaults,
+    config: &Config,
+) {
+    let mut notification = NotificationDefaults {
+        app_name: Some(
+            defaults
+                .app_name
+                .clone()
+                .unwrap_or_else(|| String::from("alertify")),
+        ),
+        ..defaults.clone()
+    }
+    .to_notification(title, body);
+    notification.icon = icon.unwrap_or_else(|| {
+        String::from(match success {
+            Some(true) => "emblem-default",
+            Some(false) => "dialog-error",
+            None => "dialog-information",
+        })
+    });
+    if success == Some(false) {
+        notification.urgency = Urgency::Critical;
+    }
+    notification.actions.clear();
+
+    let throttle = Throttle {
+        rate_limit: config.rate_limit.get(&notification.app_name).copied(),
+        ..Throttle::default()
+    };
+    if let Err(e) = send_notification(notification, &config.quiet_hours, &throttle).await {
+        eprintln!("alertify: failed to send notification: {e}");
+    }
+}
+
+/// Runs the command with its output passed through, then notifies with how it went.
+///
+/// Returns the exit code to leave with: the child's own, 128 plus the signal that killed
+/// it, or 127 if it could not be started.
+pub async fn handle_run(
+    args: RunArgs,
+    defaults: &NotificationDefaults,
+    config: &Config,
+) -> Result<i32, Box<dyn Error>> {
+    let command_line = args.command.join(" ");
+
+    // Ctrl-C reaches the child through the process group; stay alive to report on it.
+    let _interrupt = signal(SignalKind::interrupt())?;
+
+    let started = Instant::now();
+    let mut child = match Command::new(&args.command[0])
+        .args(&args.command[1..])
+        .stderr(Stdio::piped())
+        .spawn()
+    {
+        Ok(child) => child,
+        Err(e) => {
+            eprintln!("alertify: failed to run `{}`: {e}", args.command[0]);
+            // The codes a shell uses for missing and non-executable commands.
+            return Ok(if e.kind() == ErrorKind::NotFound {
+                127
+            } else {
+                126
+            });
+        }
+    };
+
+    let mut stderr = child.stderr.take().ok_or("child stderr was not captured")?;
+    let mut tail = Tail::new(args.lines);
+    let mut buffer = [0; 8192];
+    let mut stderr_open = true;
+    let status = loop {
+        tokio::select! {
+            status = child.wait() => break status?,
+            read = stderr.read(&mut buffer), if stderr_open => {
+                let read = read?;
+                stderr_open = read > 0;
+                pass_through(&mut tail, &buffer[..read])?;
+            }
+        }
+    };
+    // A background grandchild may keep stderr open long after the command exited, so
+    // only what is already there gets read.
+    let draining = async {
+        while stderr_open {
+            let read = stderr.read(&mut buffer).await?;
+            stderr_open = read > 0;
+            pass_through(&mut tail, &buffer[..read])?;
+        }
+        Ok::<(), std::io::Error>(())
+    };
+    if let Ok(result) = tokio::time::timeout(DRAIN_TIMEOUT, draining).await {
+        result?;
+    }
+
+    let elapsed = started.elapsed();
+    let (description, code) = describe_status(status);
+
+    if args.min_duration.is_some_and(|min| elapsed < min) {
+        return Ok(code);
+    }
+
+    let success = status.success();
+    let title = args.title.unwrap_or_else(|| {
+        let verdict = if success { "finished" } else { "failed" };
+        format!("{} {verdict}", shorten(&command_line))
+    });
+    let mut body = format!("{description} after {}", format_duration(elapsed));
+    let tail = tail.finish();
+    if !tail.is_empty() {
+        body.push_str("\n\n");
+        body.push_str(&tail.join("\n"));
+    }
+
+    notify_finished(title, body, Some(success), args.icon, defaults, config).await;
+
+    Ok(code)
+}
@@ -0,0 +1,194 @@
+use crate::config::{Config, NotificationDefaults};
+use crate::deliver::Sender;
+use crate::dnd::{active_reason, deliver_digest, has_deferred};
+use crate::jobs::{Job, acquire_scheduler_lock, wait_for_scheduler_lock, with_jobs};
+use crate::notification::Notification;
+use crate::throttle::{Outcome, Throttle};
+use chrono::{DateTime, Duration as ChronoDuration, Local};
+use std::error::Error;
+use std::time::Duration;
+
+/// Longest the scheduler sleeps before looking at the wall clock again. The monotonic
+/// clock stops during suspend, so a single long sleep would fire late after a resume.
+const MAX_SLEEP: Duration = Duration::from_secs(30);
+
+/// How often do-not-disturb is checked while notifications wait for it to end.
+const DEFERRED_POLL: Duration = Duration::from_secs(2);
+
+/// Jobs delivered later than this are marked with the time they were due.
+const LATE_AFTER: ChronoDuration = ChronoDuration::minutes(1);
+
+/// Recurring reminders missed by more than this, e.g. while suspended, are skipped.
+const MISSED_AFTER: ChronoDuration = ChronoDuration::minutes(10);
+
+async fn deliver_with_config(
+    sender: &mut Sender,
+    config: &Config,
+    notification: &Notification,
+) -> Result<Outcome, Box<dyn Error>> {
+    let throttle = Throttle {
+        rate_limit: config.rate_limit.get(&notification.app_name).copied(),
+        ..Throttle::default()
+    };
+    sender
+        .send(notification, &config.quiet_hours, &throttle)
+        .await
+}
+
+async fn deliver_job(
+    sender: &mut Sender,
+    config: &Config,
+    job: &Job,
+) -> Result<Outcome, Box<dyn Error>> {
+    let mut notification = job.notification.clone();
+    if Local::now() - job.due > LATE_AFTER {
+        notification.body = format!(
+            "{}\n\n(due at {})",
+            notification.body,
+            job.due.format("%H:%M")
+        );
+    }
+    deliver_with_config(sender, config, &notification).await
+}
+
+/// Fires recurring reminders that are due and moves them on to their next occurrence.
+async fn fire_reminders(
+    sender: &mut Sender,
+    config: &Config,
+    defaults: &NotificationDefaults,
+    next_fire: &mut [Option<DateTime<Local>>],
+) {
+    let now = Local::now();
+    for (reminder, next) in config.reminder.iter().zip(next_fire.iter_mut()) {
+        let Some(due) = *next else {
+            continue;
+        };
+        if due > now {
+            continue;
+        }
+
+        if now - due <= MISSED_AFTER {
+            let notification = reminder.to_notification(defaults);
+            match deliver_with_config(sender, config, &notification).await {
+                Ok(_) => println!("Fired reminder \"{}\".", reminder.title),
+                Err(e) => eprintln!("alertify: failed to fire \"{}\": {e}", reminder.title),
+            }
+        }
+        *next = reminder.next_after(now);
+    }
+}
+
+/// Delivers scheduled jobs as they come due and fires the recurring reminders from the
+/// config, until interrupted. Notifications deferred by do-not-disturb are delivered as a
+/// digest once it ends.
+///
+/// With `until_idle`, as started by `in`, `at` and deferred notifications, reminders don't
+/// fire and it exits once nothing is left, returning straight away if another scheduler
+/// is running.
+pub async fn handle_scheduler(
+    config: &Config,
+    defaults: &NotificationDefaults,
+    until_idle: bool,
+) -> Result<(), Box<dyn Error>> {
+    let mut lock = if until_idle {
+        let Some(lock) = acquire_scheduler_lock()? else {
+            println!("The scheduler is already running.");
+            return Ok(());
+        };
+        lock
+    } else {
+        match acquire_scheduler_lock()? {
+            Some(lock) => lock,
+            None => {
+                println!("Waiting for the running scheduler to finish.");
+                wait_for_scheduler_lock().await?
+            }
+        }
+    };
+
+    let mut sender = Sender::default();
+    let started = Local::now();
+    let reminders = if until_idle {
+        &[][..]
+    } else {
+        &config.reminder[..]
+    };
+    let mut next_fire: Vec<_> = reminders
+        .iter()
+        .map(|reminder| reminder.next_after(started))
+        .collect();
+
+    loop {
+        let due = with_jobs(|store| store.take_due(Local::now()))?;
+        let mut failed = Vec::new();
+        for job in due {
+            match deliver_job(&mut sender, config, &job).await {
+                Ok(_) => println!("Delivered #{} \"{}\".", job.id, job.notification.title),
+                Err(e) => {
+                    eprintln!("alertify: failed to deliver #{}: {e}", job.id);
+                    failed.push(job);
+                }
+            }
+        }
+        let retrying = !failed.is_empty();
+
+        fire_reminders(&mut sender, config, defaults, &mut next_fire).await;
+
+        // Notifications deferred by do-not-disturb wait for it to end, which nothing else
+        // notices.
+        if has_deferred() && active_reason(&config.quiet_hours)?.is_none() {
+            let digest = async { deliver_digest(sender.proxy().await?).await }.await;
+            match digest {
+                Ok(0) => {}
+                Ok(count) => println!("Delivered a digest of {count} deferred notifications."),
+                Err(e) => eprintln!("alertify: failed to deliver the digest: {e}"),
+            }
+        }
+
+        let next_job = with_jobs(|store| {
+            store.jobs.extend(failed);
+            store.next_due()
+        })?;
+        let next_due = next_job
+            .into_iter()
+            .chain(next_fire.iter().flatten().copied())
+            .min();
+        let Some(next_due) = next_due else {
+            if has_deferred() {
+                tokio::time::sleep(DEFERRED_POLL).await;
+                continue;
+            }
+            if !until_idle {
+                tokio::time::sleep(MAX_SLEEP).await;
+                continue;
+            }
+            // A job added or a notification deferred just before the lock is released would
+            // otherwise be left without a scheduler, since `ensure_scheduler` saw this one
+            // still running.
+            drop(lock);
+            if with_jobs(|store| store.jobs.is_empty())? && !has_deferred() {
+                return Ok(());
+            }
+            match acquire_scheduler_lock()? {
+                Some(relocked) => {
+                    lock = relocked;
+                    continue;
+                }
+                None => return Ok(()),
+            }
+        };
+
+        let until_due = (next_due - Local::now()).to_std().unwrap_or(Duration::ZERO);
+        let pause = if retrying {
+            MAX_SLEEP
+        } else {
+            until_due.min(MAX_SLEEP)
+        };
+        let pause = if has_deferred() {
+            pause.min(DEFERRED_POLL)
+        } else {
+            pause
+        };
+        tokio::time::sleep(pause).await;
+    }
+}
@@ -0,0 +1,417 @@
+use crate::bus;
+use crate::duration::parse_duration;
+use crate::history::close_reason_name;
+use chrono::Local;
+use clap::Args;
+use futures_lite::StreamExt;
+use serde_json::json;
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::io::{AsyncBufReadExt, BufReader};
+use tokio::runtime::Handle;
+use zbus::fdo::{DBusProxy, RequestNameFlags, RequestNameReply};
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::OwnedValue;
+use zbus::{Connection, interface};
+
+const BUS_NAME: &str = "org.freedesktop.Notifications";
+const OBJECT_PATH: &str = "/org/freedesktop/Notifications";
+
+#[derive(Args, Debug)]
+pub struct ServeArgs {
+    /// Append notifications to this file instead of printing them
+    #[arg(short, long)]
+    pub log: Option<PathBuf>,
+
+    /// Write one JSON object per event instead of text
+    #[arg(long)]
+    pub json: bool,
+
+    /// Take over from a notification server that is already running
+    #[arg(long)]
+    pub replace: bool,
+
+    /// How long notifications that leave it to the server stay open
+    #[arg(short = 't', long, value_parser = parse_duration, default_value = "5s")]
+    pub default_timeout: Duration,
+}
+
+/// Why a notification was closed, as the `NotificationClosed` signal reports it.
+#[derive(Clone, Copy, Debug)]
+enum CloseReason {
+    Expired = 1,
+    Dismissed = 2,
+    Closed = 3,
+}
+
+fn urgency_name(urgency: u8) -> &'static str {
+    match urgency {
+        0 => "low",
+        2 => "critical",
+        _ => "normal",
+    }
+}
+
+/// A notification that is open, i.e. not yet expired, dismissed or closed.
+struct Shown {
+    /// Bumped when the notification is replaced, so the old expiry timer leaves it alone.
+    generation: u64,
+    app_name: String,
+    summary: String,
+    actions: Vec<(String, String)>,
+}
+
+/// The open notifications and where they are rendered.
+struct State {
+    next_id: u32,
+    next_generation: u64,
+    shown: HashMap<u32, Shown>,
+    output: Box<dyn Write + Send>,
+    json: bool,
+}
+
+impl State {
+    fn write(&mut self, text: String, event: serde_json::Value) {
+        let line = if self.json {
+            event.to_string()
+        } else {
+            format!("[{}] {text}", Local::now().format("%H:%M:%S"))
+        };
+        if let Err(e) = writeln!(self.output, "{line}").and_then(|_| self.output.flush()) {
+            eprintln!("alertify: failed to write notification: {e}");
+        }
+    }
+
+    /// Removes the notification and renders why, returning whether it was open.
+    ///
+    /// `generation` limits this to one particular version of a replaced notification.
+    fn close(&mut self, id: u32, generation: Option<u64>, reason: CloseReason) -> bool {
+        let open = self
+            .shown
+            .get(&id)
+            .is_some_and(|shown| generation.is_none_or(|g| shown.generation == g));
+        if !open {
+            return false;
+        }
+        self.shown.remove(&id);
+        self.write(
+            format!("#{id} {}", close_reason_name(reason as u32)),
+            json!({
+                "event": "closed",
+                "time": Local::now(),
+                "id": id,
+                "reason": close_reason_name(reason as u32),
+            }),
+        );
+        true
+    }
+}
+
+struct NotificationServer {
+    state: Arc<Mutex<State>>,
+    default_timeout: Duration,
+    /// Method calls run on zbus's own executor, so timers are spawned on ours explicitly.
+    runtime: Handle,
+}
+
+#[interface(name = "org.freedesktop.Notifications")]
+impl NotificationServer {
+    #[allow(clippy::too_many_arguments)]
+    async fn notify(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        app_name: String,
+        replaces_id: u32,
+        app_icon: String,
+        summary: String,
+        body: String,
+        actions: Vec<String>,
+        hints: HashMap<String, OwnedValue>,
+        expire_timeout: i32,
+    ) -> u32 {
+        let urgency = hints
+            .get("urgency")
+            .and_then(|value| value.downcast_ref::<u8>().ok())
+            .unwrap_or(1);
+        let actions: Vec<(String, String)> = actions
+            .chunks(2)
+            .map(|pair| (pair[0].clone(), pair.get(1).cloned().unwrap_or_default()))
+            .collect();
+
+        let (id, generation) = {
+            let mut state = self.state.lock().unwrap();
+            let id = if replaces_id != 0 && state.shown.contains_key(&replaces_id) {
+                replaces_id
+            } else {
+                state.next_id += 1;
+                state.next_id
+            };
+            state.next_generation += 1;
+            let generation = state.next_generation;
+
+            let mut text = format!("#{id} {app_name}");
+            if urgency != 1 {
+                text.push_str(&format!(" ({})", urgency_name(urgency)));
+            }
+            text.push_str(&format!(": {summary}"));
+            for line in body.lines() {
+                text.push_str(&format!("\n    {line}"));
+            }
+            if !actions.is_empty() {
+                let listed: Vec<String> = actions
+                    .iter()
+                    .map(|(key, label)| format!("{key} ({label})"))
+                    .collect();
+                text.push_str(&format!("\n    actions: {}", listed.join(", ")));
+            }
+            state.write(
+                text,
+                json!({
+                    "event": "notify",
+                    "time": Local::now(),
+                    "id": id,
+                    "app_name": app_name,
+                    "icon": app_icon,
+                    "summary": summary,
+                    "body": body,
+                    "urgency": urgency_name(urgency),
+                    "actions": actions
+                        .iter()
+                        .map(|(key, label)| json!({ "key": key, "label": label }))
+                        .collect::<Vec<_>>(),
+                    "timeout": expire_timeout,
+                }),
+            );
+            state.shown.insert(
+                id,
+                Shown {
+                    generation,
+                    app_name,
+                    summary,
+                    actions,
+                },
+            );
+            (id, generation)
+        };
+
+        // Critical notifications stay until dismissed unless the sender asks otherwise.
+        let timeout = match expire_timeout {
+            0 => None,
+            t if t > 0 => Some(Duration::from_millis(t as u64)),
+            _ if urgency == 2 => None,
+            _ => Some(self.default_timeout),
+        };
+        if let Some(timeout) = timeout {
+            let state = Arc::clone(&self.state);
+            let emitter = emitter.to_owned();
+            self.runtime.spawn(async move {
+                tokio::time::sleep(timeout).await;
+                let expired =
+                    state
+                        .lock()
+                        .unwrap()
+                        .close(id, Some(generation), CloseReason::Expired);
+                if expired {
+                    let _ = NotificationServer::notification_closed(
+                        &emitter,
+                        id,
+                        CloseReason::Expired as u32,
+                    )
+                    .await;
+                }
+            });
+        }
+
+        id
+    }
+
+    async fn close_notification(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        id: u32,
+    ) -> zbus::fdo::Result<()> {
+        let closed = self
+            .state
+            .lock()
+            .unwrap()
+            .close(id, None, CloseReason::Closed);
+        if closed {
+            NotificationServer::notification_closed(&emitter, id, CloseReason::Closed as u32)
+                .await?;
+        }
+        Ok(())
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        vec![String::from("actions"), String::from("body")]
+    }
+
+    fn get_server_information(&self) -> (String, String, String, String) {
+        (
+            String::from("alertify"),
+            String::from("alertify"),
+            String::from(env!("CARGO_PKG_VERSION")),
+            String::from("1.2"),
+        )
+    }
+
+    #[zbus(signal)]
+    async fn action_invoked(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        action_key: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn notification_closed(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        reason: u32,
+    ) -> zbus::Result<()>;
+}
+
+/// Runs one command typed on stdin: `invoke ID [ACTION]`, `dismiss ID` or `list`.
+async fn run_command(
+    line: &str,
+    state: &Mutex<State>,
+    emitter: &SignalEmitter<'_>,
+) -> Result<(), Box<dyn Error>> {
+    let words: Vec<&str> = line.split_whitespace().collect();
+    let id = |index: usize| -> Result<u32, Box<dyn Error>> {
+        let id = words.get(index).ok_or("missing notification ID")?;
+        Ok(id.trim_start_matches('#').parse()?)
+    };
+
+    match words.as_slice() {
+        [] => {}
+        ["list"] => {
+            let state = state.lock().unwrap();
+            let mut ids: Vec<&u32> = state.shown.keys().collect();
+            ids.sort();
+            for id in ids {
+                let shown = &state.shown[id];
+                eprintln!("#{id} {}: {}", shown.app_name, shown.summary);
+            }
+        }
+        ["invoke", ..] => {
+            let id = id(1)?;
+            let key = words.get(2).copied().unwrap_or("default");
+            {
+                let mut state = state.lock().unwrap();
+                let shown = state
+                    .shown
+                    .get(&id)
+                    .ok_or_else(|| format!("no open notification #{id}"))?;
+                if !shown.actions.iter().any(|(action, _)| action == key) {
+                    return Err(format!("notification #{id} has no action `{key}`").into());
+                }
+                state.write(
+                    format!("#{id} action {key}"),
+                    json!({ "event": "action", "time": Local::now(), "id": id, "action": key }),
+                );
+            }
+            NotificationServer::action_invoked(emitter, id, key).await?;
+            dismiss(id, state, emitter).await?;
+        }
+        ["dismiss", ..] => {
+            let id = id(1)?;
+            if !dismiss(id, state, emitter).await? {
+                return Err(format!("no open notification #{id}").into());
+            }
+        }
+        _ => return Err("expected `invoke ID [ACTION]`, `dismiss ID` or `list`".into()),
+    }
+    Ok(())
+}
+
+async fn dismiss(id: u32, state: &Mutex<State>, emitter: &SignalEmitter<'_>) -> zbus::Result<bool> {
+    let dismissed = state
+        .lock()
+        .unwrap()
+        .close(id, None, CloseReason::Dismissed);
+    if dismissed {
+        NotificationServer::notification_closed(emitter, id, CloseReason::Dismissed as u32).await?;
+    }
+    Ok(dismissed)
+}
+
+/// Claims `org.freedesktop.Notifications` and renders what is sent to it until interrupted.
+///
+/// Lines on stdin act as the user: `invoke ID [ACTION]`, `dismiss ID` and `list`.
+pub async fn handle_serve(args: ServeArgs) -> Result<(), Box<dyn Error>> {
+    let output: Box<dyn Write + Send> = match &args.log {
+        Some(path) => Box::new(
+            OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .map_err(|e| format!("failed to open {}: {e}", path.display()))?,
+        ),
+        None => Box::new(std::io::stdout()),
+    };
+    let state = Arc::new(Mutex::new(State {
+        next_id: 0,
+        next_generation: 0,
+        shown: HashMap::new(),
+        output,
+        json: args.json,
+    }));
+
+    let connection: Connection = bus::session_builder()?
+        .serve_at(
+            OBJECT_PATH,
+            NotificationServer {
+                state: Arc::clone(&state),
+                default_timeout: args.default_timeout,
+                runtime: Handle::current(),
+            },
+        )?
+        .build()
+        .await?;
+
+    let mut name_lost = DBusProxy::new(&connection)
+        .await?
+        .receive_name_lost()
+        .await?;
+    let mut flags = RequestNameFlags::AllowReplacement | RequestNameFlags::DoNotQueue;
+    if args.replace {
+        flags |= RequestNameFlags::ReplaceExisting;
+    }
+    let taken =
+        || format!("another notification server owns {BUS_NAME}, pass --replace to take over");
+    match connection.request_name_with_flags(BUS_NAME, flags).await {
+        Ok(RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner) => {}
+        Ok(RequestNameReply::Exists | RequestNameReply::InQueue) | Err(zbus::Error::NameTaken) => {
+            return Err(taken().into());
+        }
+        Err(e) => return Err(e.into()),
+    }
+    eprintln!("Serving {BUS_NAME}, press Ctrl-C to stop.");
+
+    let emitter = SignalEmitter::new(&connection, OBJECT_PATH)?.into_owned();
+    tokio::spawn(async move {
+        let mut lines = BufReader::new(tokio::io::stdin()).lines();
+        while let Ok(Some(line)) = lines.next_line().await {
+            if let Err(e) = run_command(&line, &state, &emitter).await {
+                eprintln!("alertify: {e}");
+            }
+        }
+    });
+
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        lost = name_lost.next() => {
+            return Err(match lost {
+                Some(_) => format!("another notification server took over {BUS_NAME}"),
+                None => String::from("the session bus went away"),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,417 @@
+use crate::bus;
+use crate::clock::TimeWindow;
+use crate::config::NotificationDefaults;
+use crate::deliver::deliver;
+use crate::dnd::{DndReason, active_reason, deliver_digest, set_enabled};
+use crate::duration::format_duration;
+use crate::history::{Fate, SentNotifications, entries, load_records};
+use crate::jobs::{JobStore, ensure_scheduler, jobs_path, read_jobs, with_jobs};
+use crate::notification::{ActionInvokedStream, NotificationClosedStream, NotificationsProxy};
+use crate::paths::state_dir;
+use crate::throttle::{Outcome, Throttle};
+use chrono::{DateTime, Local};
+use futures_lite::StreamExt;
+use inotify::{Inotify, WatchMask};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::runtime::Handle;
+use tokio::task::JoinHandle;
+use zbus::fdo::{self, DBusProxy, RequestNameFlags, RequestNameReply};
+use zbus::interface;
+use zbus::object_server::SignalEmitter;
+
+const BUS_NAME: &str = "io.github.alertify";
+const OBJECT_PATH: &str = "/io/github/alertify";
+
+/// How often do-not-disturb is checked for changes that touch no file: quiet hours
+/// starting or ending, and a pomodoro holding it exiting.
+const DND_POLL: Duration = Duration::from_secs(5);
+
+/// A reminder as `ListReminders` returns it: ID, due time in Unix seconds and title.
+type ReminderInfo = (u32, i64, String);
+
+/// A timer as `ListTimers` returns it: ID, title and end time in Unix seconds.
+type TimerInfo = (u32, String, i64);
+
+/// A sent notification as `QueryHistory` returns it: ID, Unix time, app name, title,
+/// body, invoked action and close reason, with the last two empty when unknown.
+type HistoryInfo = (u32, i64, String, String, String, String, String);
+
+struct Timer {
+    title: String,
+    ends_at: DateTime<Local>,
+    task: JoinHandle<()>,
+}
+
+#[derive(Default)]
+struct Timers {
+    next_id: u32,
+    running: HashMap<u32, Timer>,
+}
+
+struct AlertifyService {
+    proxy: NotificationsProxy<'static>,
+    defaults: NotificationDefaults,
+    quiet_hours: Vec<TimeWindow>,
+    timers: Arc<Mutex<Timers>>,
+    /// Timer notifications, whose action and close go into the history.
+    sent: Arc<Mutex<SentNotifications>>,
+    runtime: Handle,
+}
+
+fn failed(e: Box<dyn Error>) -> fdo::Error {
+    fdo::Error::Failed(e.to_string())
+}
+
+/// Runs `f` on the job store on a blocking thread, since the store is locked with flock.
+async fn on_jobs<T: Send + 'static>(
+    runtime: &Handle,
+    f: impl FnOnce(&mut JobStore) -> T + Send + 'static,
+) -> fdo::Result<T> {
+    runtime
+        .spawn_blocking(move || with_jobs(f).map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| fdo::Error::Failed(e.to_string()))?
+        .map_err(fdo::Error::Failed)
+}
+
+/// The pending reminders, soonest first, read on a blocking thread like `on_jobs`.
+async fn reminders(runtime: &Handle) -> Result<Vec<ReminderInfo>, String> {
+    let read = || -> Result<Vec<ReminderInfo>, Box<dyn Error>> {
+        let mut jobs = read_jobs()?;
+        jobs.sort_by_key(|job| job.due);
+        Ok(jobs
+            .into_iter()
+            .map(|job| (job.id, job.due.timestamp(), job.notification.title))
+            .collect())
+    };
+    runtime
+        .spawn_blocking(move || read().map_err(|e| e.to_string()))
+        .await
+        .map_err(|e| e.to_string())?
+}
+
+fn dnd_active(quiet_hours: &[TimeWindow]) -> Result<bool, Box<dyn Error>> {
+    Ok(active_reason(quiet_hours)?.is_some())
+}
+
+#[interface(name = "io.github.alertify")]
+impl AlertifyService {
+    /// Schedules a reminder for `due`, in Unix seconds, and returns its ID.
+    async fn schedule_reminder(&self, title: String, body: String, due: i64) -> fdo::Result<u32> {
+        let due = DateTime::from_timestamp(due, 0)
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("invalid time {due}")))?
+            .with_timezone(&Local);
+        let notification = self.defaults.to_notification(title, body);
+        let id = on_jobs(&self.runtime, move |store| store.add(due, notification)).await?;
+        ensure_scheduler().map_err(failed)?;
+        Ok(id)
+    }
+
+    async fn list_reminders(&self) -> fdo::Result<Vec<ReminderInfo>> {
+        reminders(&self.runtime).await.map_err(fdo::Error::Failed)
+    }
+
+    async fn cancel_reminder(&self, id: u32) -> fdo::Result<()> {
+        let cancelled = on_jobs(&self.runtime, move |store| {
+            let before = store.jobs.len();
+            store.jobs.retain(|job| job.id != id);
+            store.jobs.len() != before
+        })
+        .await?;
+        if !cancelled {
+            return Err(fdo::Error::InvalidArgs(format!(
+                "no pending reminder with ID {id}"
+            )));
+        }
+        Ok(())
+    }
+
+    /// Turns manual do-not-disturb on or off. Turning it off delivers the digest of
+    /// notifications held back meanwhile.
+    async fn set_do_not_disturb(&self, enabled: bool) -> fdo::Result<()> {
+        set_enabled(enabled).map_err(failed)?;
+        if !enabled {
+            // On the runtime, which waits for the outbox's lock on a blocking thread.
+            let proxy = self.proxy.clone();
+            self.runtime
+                .spawn(async move { deliver_digest(&proxy).await.map_err(|e| e.to_string()) })
+                .await
+                .map_err(|e| fdo::Error::Failed(e.to_string()))?
+                .map_err(fdo::Error::Failed)?;
+        }
+        Ok(())
+    }
+
+    /// Whether notifications are held back, and why: `manual`, `quiet_hours` or empty.
+    fn do_not_disturb(&self) -> fdo::Result<(bool, String)> {
+        Ok(match active_reason(&self.quiet_hours).map_err(failed)? {
+            Some(DndReason::Manual) => (true, String::from("manual")),
+            Some(DndReason::QuietHours) => (true, String::from("quiet_hours")),
+            None => (false, String::new()),
+        })
+    }
+
+    /// Starts a timer that sends a notification titled `title` after `seconds`, and
+    /// returns its ID.
+    async fn start_timer(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        title: String,
+        seconds: u32,
+    ) -> fdo::Result<u32> {
+        let length = Duration::from_secs(seconds.into());
+        let ends_at = Local::now() + chrono::Duration::seconds(seconds.into());
+        let mut notification = self.defaults.to_notification(
+            title.clone(),
+            format!("{} timer done", format_duration(length)),
+        );
+        notification.actions.clear();
+        let quiet_hours = self.quiet_hours.clone();
+
+        // Held until the timer is in the list, so a short one can't finish before it.
+        let id = {
+            let mut timers = self.timers.lock().unwrap();
+            timers.next_id += 1;
+            let id = timers.next_id;
+            let proxy = self.proxy.clone();
+            let finished = emitter.to_owned();
+            let state = Arc::clone(&self.timers);
+            let sent = Arc::clone(&self.sent);
+            let task = self.runtime.spawn(async move {
+                tokio::time::sleep(length).await;
+                state.lock().unwrap().running.remove(&id);
+                match deliver(&proxy, &notification, &quiet_hours, &Throttle::default()).await {
+                    Ok(Outcome::Sent(sent_id) | Outcome::Collapsed { id: sent_id, .. }) => {
+                        for fate in sent.lock().unwrap().insert(sent_id) {
+                            fate.record(sent_id);
+                        }
+                    }
+                    Ok(_) => {}
+                    Err(e) => eprintln!("alertify: failed to send timer notification: {e}"),
+                }
+                let _ = AlertifyService::timer_finished(&finished, id).await;
+            });
+            timers.running.insert(
+                id,
+                Timer {
+                    title: title.clone(),
+                    ends_at,
+                    task,
+                },
+            );
+            id
+        };
+
+        AlertifyService::timer_started(&emitter, id, &title, ends_at.timestamp()).await?;
+        Ok(id)
+    }
+
+    async fn cancel_timer(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        id: u32,
+    ) -> fdo::Result<()> {
+        let timer = self.timers.lock().unwrap().running.remove(&id);
+        let timer = timer
+            .ok_or_else(|| fdo::Error::InvalidArgs(format!("no running timer with ID {id}")))?;
+        timer.task.abort();
+        AlertifyService::timer_cancelled(&emitter, id).await?;
+        Ok(())
+    }
+
+    fn list_timers(&self) -> Vec<TimerInfo> {
+        let timers = self.timers.lock().unwrap();
+        let mut running: Vec<TimerInfo> = timers
+            .running
+            .iter()
+            .map(|(id, timer)| (*id, timer.title.clone(), timer.ends_at.timestamp()))
+            .collect();
+        running.sort_by_key(|timer| timer.2);
+        running
+    }
+
+    /// The most recent `limit` notifications, oldest first, from `app` or every app when
+    /// it is empty. A `limit` of 0 returns them all.
+    fn query_history(&self, app: String, limit: u32) -> fdo::Result<Vec<HistoryInfo>> {
+        let mut matched: Vec<HistoryInfo> = entries(load_records().map_err(failed)?)
+            .into_iter()
+            .filter(|entry| app.is_empty() || entry.notification.app_name == app)
+            .map(|entry| {
+                (
+                    entry.id,
+                    entry.timestamp.timestamp(),
+                    entry.notification.app_name,
+                    entry.notification.title,
+                    entry.notification.body,
+                    entry.action.unwrap_or_default(),
+                    entry.close_reason.unwrap_or_default().to_string(),
+                )
+            })
+            .collect();
+        if limit > 0 {
+            let skip = matched.len().saturating_sub(limit as usize);
+            matched.drain(..skip);
+        }
+        Ok(matched)
+    }
+
+    /// Emitted whenever a reminder is added, cancelled or delivered, by anyone.
+    #[zbus(signal)]
+    async fn reminders_changed(emitter: &SignalEmitter<'_>) -> zbus::Result<()>;
+
+    /// Emitted whenever notifications start or stop being held back, whether by
+    /// `alertify dnd`, a pomodoro or quiet hours.
+    #[zbus(signal)]
+    async fn do_not_disturb_changed(emitter: &SignalEmitter<'_>, enabled: bool)
+    -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn timer_started(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        title: &str,
+        ends_at: i64,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn timer_finished(emitter: &SignalEmitter<'_>, id: u32) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn timer_cancelled(emitter: &SignalEmitter<'_>, id: u32) -> zbus::Result<()>;
+}
+
+/// Emits `RemindersChanged` and `DoNotDisturbChanged` whenever the state behind them
+/// changes, whoever changed it: this service, another alertify command or the scheduler.
+/// The files in the state directory are watched, and do-not-disturb is polled as well.
+async fn announce_changes(
+    emitter: SignalEmitter<'static>,
+    quiet_hours: Vec<TimeWindow>,
+) -> Result<(), Box<dyn Error>> {
+    let dir = state_dir();
+    fs::create_dir_all(&dir)?;
+    let inotify = Inotify::init()?;
+    inotify.watches().add(
+        &dir,
+        WatchMask::CLOSE_WRITE | WatchMask::MOVED_TO | WatchMask::DELETE,
+    )?;
+    let mut events = inotify.into_event_stream([0; 4096])?;
+    let jobs_file = jobs_path().file_name().map(|name| name.to_os_string());
+
+    let runtime = Handle::current();
+    let mut last_reminders = reminders(&runtime).await.unwrap_or_default();
+    let mut last_dnd = dnd_active(&quiet_hours).unwrap_or(false);
+    let mut poll = tokio::time::interval(DND_POLL);
+    loop {
+        let jobs_touched = tokio::select! {
+            event = events.next() => {
+                let event = event.ok_or("the state directory watch ended")??;
+                event.name.is_some_and(|name| Some(name) == jobs_file)
+            }
+            _ = poll.tick() => false,
+        };
+
+        if jobs_touched {
+            match reminders(&runtime).await {
+                Ok(reminders) if reminders != last_reminders => {
+                    last_reminders = reminders;
+                    AlertifyService::reminders_changed(&emitter).await?;
+                }
+                Ok(_) => {}
+                Err(e) => eprintln!("alertify: failed to read the reminders: {e}"),
+            }
+        }
+        match dnd_active(&quiet_hours) {
+            Ok(dnd) if dnd != last_dnd => {
+                last_dnd = dnd;
+                AlertifyService::do_not_disturb_changed(&emitter, dnd).await?;
+            }
+            Ok(_) => {}
+            Err(e) => eprintln!("alertify: failed to read do-not-disturb: {e}"),
+        }
+    }
+}
+
+/// Records the actions and closes of the service's own notifications in the history,
+/// until the bus goes away.
+async fn record_fates(
+    mut invoked: ActionInvokedStream,
+    mut closed: NotificationClosedStream,
+    sent: Arc<Mutex<SentNotifications>>,
+) -> Result<(), Box<dyn Error>> {
+    loop {
+        let (id, fate) = tokio::select! {
+            // An action comes before the close it causes.
+            biased;
+            Some(signal) = invoked.next() => {
+                let signal = signal.args()?;
+                (signal.id, Fate::Action(signal.action_key.to_string()))
+            }
+            Some(signal) = closed.next() => {
+                let signal = signal.args()?;
+                (signal.id, Fate::Closed(signal.reason))
+            }
+            else => return Err("the session bus went away".into()),
+        };
+        if sent.lock().unwrap().signalled(id, &fate) {
+            fate.record(id);
+        }
+    }
+}
+
+/// Claims `io.github.alertify` on the session bus and serves reminders, do-not-disturb,
+/// timers and history to other programs until interrupted.
+pub async fn handle_service(
+    defaults: &NotificationDefaults,
+    quiet_hours: &[TimeWindow],
+) -> Result<(), Box<dyn Error>> {
+    let connection = bus::session().await?;
+    let proxy = NotificationsProxy::new(&connection).await?;
+    let sent = Arc::new(Mutex::new(SentNotifications::default()));
+    // Subscribed before any timer can start.
+    let invoked = proxy.receive_action_invoked().await?;
+    let closed = proxy.receive_notification_closed().await?;
+    let service = AlertifyService {
+        proxy: proxy.clone(),
+        defaults: defaults.clone(),
+        quiet_hours: quiet_hours.to_vec(),
+        timers: Arc::new(Mutex::new(Timers::default())),
+        sent: Arc::clone(&sent),
+        runtime: Handle::current(),
+    };
+    connection.object_server().at(OBJECT_PATH, service).await?;
+
+    let mut name_lost = DBusProxy::new(&connection)
+        .await?
+        .receive_name_lost()
+        .await?;
+    match connection
+        .request_name_with_flags(BUS_NAME, RequestNameFlags::DoNotQueue.into())
+        .await
+    {
+        Ok(RequestNameReply::PrimaryOwner | RequestNameReply::AlreadyOwner) => {}
+        Ok(RequestNameReply::Exists | RequestNameReply::InQueue) | Err(zbus::Error::NameTaken) => {
+            return Err(format!("another alertify service already owns {BUS_NAME}").into());
+        }
+        Err(e) => return Err(e.into()),
+    }
+    eprintln!("Serving {BUS_NAME}, press Ctrl-C to stop.");
+
+    let emitter = SignalEmitter::new(&connection, OBJECT_PATH)?.into_owned();
+    tokio::select! {
+        result = tokio::signal::ctrl_c() => result?,
+        result = announce_changes(emitter, quiet_hours.to_vec()) => result?,
+        result = record_fates(invoked, closed, sent) => result?,
+        lost = name_lost.next() => {
+            return Err(match lost {
+                Some(_) => format!("lost {BUS_NAME} on the session bus"),
+                None => String::from("the session bus went away"),
+            }
+            .into());
+        }
+    }
+    Ok(())
+}
@@ -0,0 +1,252 @@
+use crate::config::{Config, NotificationDefaults};
+use crate::deliver::Sender;
+use crate::notification::{HintValue, Notification, Urgency};
+use crate::throttle::{Outcome, Throttle};
+use clap::ValueEnum;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::io::Read;
+use std::path::Path;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum SpecFormat {
+    Json,
+    Toml,
+}
+
+/// A notification as written in a JSON or TOML document.
+///
+/// Omitted fields fall back to the configured defaults, while omitted `actions`
+/// keep the built-in action set.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct NotificationSpec {
+    #[serde(default)]
+    pub app_name: Option<String>,
+    #[serde(default)]
+    pub replaces_id: u32,
+    pub title: String,
+    #[serde(default)]
+    pub body: String,
+    #[serde(default)]
+    pub icon: Option<String>,
+    #[serde(default, deserialize_with = "crate::duration::deserialize_timeout")]
+    pub timeout: Option<i32>,
+    #[serde(default)]
+    pub urgency: Option<Urgency>,
+    #[serde(default)]
+    pub category: Option<String>,
+    #[serde(default)]
+    pub transient: bool,
+    #[serde(default)]
+    pub actions: Option<Vec<ActionSpec>>,
+    #[serde(default)]
+    pub hints: HashMap<String, HintSpec>,
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct ActionSpec {
+    pub key: String,
+    pub label: String,
+}
+
+/// Hint values are typed by their JSON/TOML type; bytes are written as `{ byte = N }`.
+#[derive(Debug, Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum HintSpec {
+    Bool(bool),
+    Int(i32),
+    Double(f64),
+    Str(String),
+    Byte { byte: u8 },
+}
+
+impl From<HintSpec> for HintValue {
+    fn from(hint: HintSpec) -> Self {
+        match hint {
+            HintSpec::Bool(v) => HintValue::Bool(v),
+            HintSpec::Int(v) => HintValue::Int(v),
+            HintSpec::Double(v) => HintValue::Double(v),
+            HintSpec::Str(v) => HintValue::Str(v),
+            HintSpec::Byte { byte } => HintValue::Byte(byte),
+        }
+    }
+}
+
+impl From<&HintValue> for HintSpec {
+    fn from(hint: &HintValue) -> Self {
+        match hint {
+            HintValue::Bool(v) => HintSpec::Bool(*v),
+            HintValue::Int(v) => HintSpec::Int(*v),
+            HintValue::Double(v) => HintSpec::Double(*v),
+            HintValue::Str(v) => HintSpec::Str(v.clone()),
+            HintValue::Byte(v) => HintSpec::Byte { byte: *v },
+        }
+    }
+}
+
+/// Writes a notification back out as a spec that rebuilds it exactly.
+impl From<&Notification> for NotificationSpec {
+    fn from(notification: &Notification) -> Self {
+        NotificationSpec {
+            app_name: Some(notification.app_name.clone()),
+            replaces_id: notification.replaces_id,
+            title: notification.title.clone(),
+            body: notification.body.clone(),
+            icon: Some(notification.icon.clone()),
+            timeout: Some(notification.timeout),
+            urgency: Some(notification.urgency),
+            category: notification.category.clone(),
+            transient: notification.transient,
+            actions: Some(
+                notification
+                    .actions
+                    .iter()
+                    .map(|(key, label)| ActionSpec {
+                        key: key.clone(),
+                        label: label.clone(),
+                    })
+                    .collect(),
+            ),
+            hints: notification
+                .hints
+                .iter()
+                .map(|(name, value)| (name.clone(), value.into()))
+                .collect(),
+        }
+    }
+}
+
+impl NotificationSpec {
+    pub fn into_notification(self, defaults: &NotificationDefaults) -> Notification {
+        let mut notification = NotificationDefaults {
+            app_name: self.app_name,
+            icon: self.icon,
+            timeout: self.timeout,
+            urgency: self.urgency,
+        }
+        .or(defaults.clone())
+        .to_notification(self.title, self.body);
+        notification.replaces_id = self.replaces_id;
+        notification.category = self.category;
+        notification.transient = self.transient;
+        if let Some(actions) = self.actions {
+            notification.actions = actions
+                .into_iter()
+                .map(|action| (action.key, action.label))
+                .collect();
+        }
+        notification
+            .hints
+            .extend(self.hints.into_iter().map(|(k, v)| (k, v.into())));
+        notification
+    }
+}
+
+/// Reads the document at `from`, where `-` means stdin.
+fn read_source(from: &str) -> Result<String, Box<dyn Error>> {
+    let mut source = String::new();
+    if from == "-" {
+        std::io::stdin().read_to_string(&mut source)?;
+    } else {
+        source = std::fs::read_to_string(from)?;
+    }
+    Ok(source)
+}
+
+fn detect_format(from: &str, source: &str) -> SpecFormat {
+    match Path::new(from).extension().and_then(|ext| ext.to_str()) {
+        Some("toml") => SpecFormat::Toml,
+        Some("json") => SpecFormat::Json,
+        _ => match source.trim_start().chars().next() {
+            Some('{') | Some('[') => SpecFormat::Json,
+            _ => SpecFormat::Toml,
+        },
+    }
+}
+
+/// Parses a document into one result per notification it contains.
+///
+/// JSON documents hold a single object or an array of objects. TOML documents
+/// hold a single notification or an array of `[[notification]]` tables.
+pub fn parse_specs(
+    source: &str,
+    format: SpecFormat,
+) -> Result<Vec<Result<NotificationSpec, String>>, Box<dyn Error>> {
+    match format {
+        SpecFormat::Json => {
+            let items = match serde_json::from_str(source)? {
+                serde_json::Value::Array(items) => items,
+                item => vec![item],
+            };
+            Ok(items
+                .into_iter()
+                .map(|item| serde_json::from_value(item).map_err(|e| e.to_string()))
+                .collect())
+        }
+        SpecFormat::Toml => {
+            let mut table: toml::Table = toml::from_str(source)?;
+            let items = match table.remove("notification") {
+                Some(toml::Value::Array(items)) => items,
+                Some(_) => return Err("`notification` must be an array of tables".into()),
+                None => vec![toml::Value::Table(table)],
+            };
+            Ok(items
+                .into_iter()
+                .map(|item| item.try_into().map_err(|e: toml::de::Error| e.to_string()))
+                .collect())
+        }
+    }
+}
+
+pub async fn handle_send(
+    from: String,
+    format: Option<SpecFormat>,
+    defaults: &NotificationDefaults,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let source = read_source(&from)?;
+    let format = format.unwrap_or_else(|| detect_format(&from, &source));
+    let specs = parse_specs(&source, format)?;
+
+    let mut sender = Sender::default();
+    let total = specs.len();
+    let mut failed = 0;
+    for (index, spec) in specs.into_iter().enumerate() {
+        let result = match spec {
+            Ok(spec) => {
+                let notification = spec.into_notification(defaults);
+                let throttle = Throttle {
+                    rate_limit: config.rate_limit.get(&notification.app_name).copied(),
+                    ..Throttle::default()
+                };
+                sender
+                    .send(&notification, &config.quiet_hours, &throttle)
+                    .await
+                    .map_err(|e| e.to_string())
+            }
+            Err(e) => Err(e),
+        };
+        match result {
+            Ok(Outcome::Sent(id)) => println!("[{index}] sent (id {id})"),
+            Ok(Outcome::Collapsed { id, suppressed }) => {
+                println!("[{index}] rate limited, collapsed into id {id} ({suppressed} suppressed)")
+            }
+            Ok(Outcome::Duplicate { .. }) => println!("[{index}] duplicate, suppressed"),
+            Ok(Outcome::Deferred) => println!("[{index}] deferred (do not disturb)"),
+            Ok(Outcome::Spooled) => println!("[{index}] spooled (no notification server)"),
+            Err(e) => {
+                failed += 1;
+                println!("[{index}] failed: {e}");
+            }
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!("{failed} of {total} notifications failed").into());
+    }
+
+    Ok(())
+}
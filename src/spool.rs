@@ -0,0 +1,205 @@
+use crate::bus;
+use crate::clock::TimeWindow;
+use crate::deliver::try_deliver;
+use crate::jobs::resume_due_jobs;
+use crate::notification::{Notification, NotificationsProxy};
+use crate::paths::state_dir;
+use crate::throttle::Throttle;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+/// D-Bus errors that mean nothing is there to take the notification right now.
+const UNREACHABLE_ERRORS: &[&str] = &[
+    "org.freedesktop.DBus.Error.ServiceUnknown",
+    "org.freedesktop.DBus.Error.NameHasNoOwner",
+    "org.freedesktop.DBus.Error.NoReply",
+    "org.freedesktop.DBus.Error.Timeout",
+    "org.freedesktop.DBus.Error.Disconnected",
+];
+
+/// Whether failed sends are spooled, set from the `spool` config option.
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// A notification that couldn't be delivered when it was sent.
+#[derive(Debug, Serialize, Deserialize)]
+struct Spooled {
+    timestamp: DateTime<Local>,
+    notification: Notification,
+}
+
+fn spool_dir() -> PathBuf {
+    state_dir().join("spool")
+}
+
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+pub fn is_enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Whether `error` means no notification server could be reached, as opposed to one
+/// rejecting the notification.
+pub fn is_unreachable(error: &(dyn Error + 'static)) -> bool {
+    match error.downcast_ref::<zbus::Error>() {
+        Some(zbus::Error::InputOutput(_) | zbus::Error::Address(_) | zbus::Error::Handshake(_)) => {
+            true
+        }
+        Some(zbus::Error::MethodError(name, ..)) => {
+            UNREACHABLE_ERRORS.contains(&name.as_str())
+                || name.starts_with("org.freedesktop.DBus.Error.Spawn.")
+        }
+        _ => false,
+    }
+}
+
+/// Keeps `notification` for a later delivery, one file per notification so concurrent
+/// invocations never interleave.
+pub fn spool(notification: &Notification) -> Result<(), Box<dyn Error>> {
+    let timestamp = Local::now();
+    let contents = serde_json::to_vec(&Spooled {
+        timestamp,
+        notification: notification.clone(),
+    })?;
+    fs::create_dir_all(spool_dir())?;
+    let name = format!(
+        "{}-{}",
+        timestamp.format("%Y%m%dT%H%M%S%.9f"),
+        std::process::id()
+    );
+    // Written aside first so a flush never reads a half-written file.
+    let partial = spool_dir().join(format!("{name}.tmp"));
+    fs::write(&partial, contents)?;
+    fs::rename(partial, spool_dir().join(format!("{name}.json")))?;
+    Ok(())
+}
+
+/// Spooled files, oldest first.
+fn spooled_files() -> Result<Vec<PathBuf>, Box<dyn Error>> {
+    let entries = match fs::read_dir(spool_dir()) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+    let mut files: Vec<PathBuf> = entries
+        .flatten()
+        .map(|entry| entry.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+        .collect();
+    files.sort();
+    Ok(files)
+}
+
+pub fn pending() -> Result<usize, Box<dyn Error>> {
+    Ok(spooled_files()?.len())
+}
+
+/// Holds the spool for one flush, so two invocations don't deliver the same file. A flush
+/// can take a while, so the wait happens on a blocking thread.
+async fn lock_spool() -> Result<File, Box<dyn Error>> {
+    fs::create_dir_all(spool_dir())?;
+    let file = OpenOptions::new()
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(spool_dir().join("lock"))?;
+    let file = tokio::task::spawn_blocking(move || file.lock().map(|()| file)).await??;
+    Ok(file)
+}
+
+/// Delivers everything in the spool, oldest first, with the time it was originally sent
+/// added to the body. Stops once no server can be reached, leaving the rest spooled, and
+/// sets aside entries the server rejects.
+pub async fn flush(
+    proxy: &NotificationsProxy<'_>,
+    quiet_hours: &[TimeWindow],
+) -> Result<usize, Box<dyn Error>> {
+    let _lock = lock_spool().await?;
+
+    let mut delivered = 0;
+    for path in spooled_files()? {
+        let spooled: Spooled = match serde_json::from_slice(&fs::read(&path)?) {
+            Ok(spooled) => spooled,
+            Err(e) => {
+                eprintln!(
+                    "alertify: setting aside unreadable spool entry {}: {e}",
+                    path.display()
+                );
+                fs::rename(&path, path.with_extension("bad"))?;
+                continue;
+            }
+        };
+        let mut notification = spooled.notification;
+        notification.body = format!(
+            "{}\n\n(sent at {})",
+            notification.body,
+            spooled.timestamp.format("%Y-%m-%d %H:%M")
+        );
+        match try_deliver(proxy, &notification, quiet_hours, &Throttle::default()).await {
+            Ok(_) => {
+                fs::remove_file(&path)?;
+                delivered += 1;
+            }
+            Err(e) if is_unreachable(e.as_ref()) => return Err(e),
+            Err(e) => {
+                // Sending it again would only fail again and hold up everything after it.
+                eprintln!(
+                    "alertify: setting aside spool entry {} the server rejected: {e}",
+                    path.display()
+                );
+                fs::rename(&path, path.with_extension("bad"))?;
+            }
+        }
+    }
+    Ok(delivered)
+}
+
+/// Delivers spooled notifications if a notification server is reachable, staying quiet if
+/// it still isn't, and starts the scheduler for jobs that came due while it wasn't running.
+/// Run at the start of commands that send.
+pub async fn deliver_pending(quiet_hours: &[TimeWindow]) -> Result<(), Box<dyn Error>> {
+    resume_due_jobs()?;
+    if pending()? == 0 {
+        return Ok(());
+    }
+    let Ok(connection) = bus::session().await else {
+        return Ok(());
+    };
+    let proxy = NotificationsProxy::new(&connection).await?;
+    match flush(&proxy, quiet_hours).await {
+        Ok(0) => {}
+        Ok(count) => eprintln!(
+            "Delivered {count} spooled notification{}.",
+            if count == 1 { "" } else { "s" }
+        ),
+        Err(e) if is_unreachable(e.as_ref()) => {}
+        Err(e) => eprintln!("alertify: failed to deliver spooled notifications: {e}"),
+    }
+    Ok(())
+}
+
+/// Delivers spooled notifications now, failing if no notification server is reachable.
+pub async fn handle_flush(quiet_hours: &[TimeWindow]) -> Result<(), Box<dyn Error>> {
+    let count = pending()?;
+    if count == 0 {
+        println!("The spool is empty.");
+        return Ok(());
+    }
+    let delivered = async {
+        let connection = bus::session().await?;
+        let proxy = NotificationsProxy::new(&connection).await?;
+        flush(&proxy, quiet_hours).await
+    }
+    .await
+    .map_err(|e| format!("could not deliver spooled notifications, they stay queued: {e}"))?;
+    println!(
+        "Delivered {delivered} spooled notification{}.",
+        if delivered == 1 { "" } else { "s" }
+    );
+    Ok(())
+}
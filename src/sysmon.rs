@@ -0,0 +1,575 @@
+use crate::config::{Config, NotificationDefaults};
+use crate::deliver::Sender;
+use crate::icons::status::STD_STATUS_ICONS;
+use crate::icons::utils::find_icon;
+use crate::notification::{DEFAULT_ICON, Urgency};
+use crate::throttle::Throttle;
+use clap::Args;
+use serde::de::{DeserializeOwned, Error as _};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::error::Error;
+use std::ffi::CString;
+use std::fs;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// Thresholds for `alertify monitor-system`.
+///
+/// ```toml
+/// [monitor]
+/// interval = "1m"
+///
+/// [monitor.battery]
+/// warn = 20
+/// critical = 10
+///
+/// [monitor.disk]
+/// paths = ["/", "/home"]
+/// warn = 90
+///
+/// [monitor.temperature]
+/// enabled = false
+/// ```
+///
+/// An alert fires when a value crosses `warn` or `critical`, and fires again only after
+/// the value has recovered by `hysteresis`. Each monitor is on with its defaults unless
+/// its section says `enabled = false`.
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MonitorConfig {
+    #[serde(deserialize_with = "crate::duration::deserialize")]
+    pub interval: Duration,
+    /// Directory that `/sys`, `/proc` and disk paths are resolved under, for testing
+    /// against a fake tree.
+    pub root: PathBuf,
+    /// Battery charge left, in percent, while discharging.
+    #[serde(deserialize_with = "deserialize_section")]
+    pub battery: Option<Threshold>,
+    /// Disk space used, in percent.
+    #[serde(deserialize_with = "deserialize_section")]
+    pub disk: Option<DiskThreshold>,
+    /// Memory used, in percent.
+    #[serde(deserialize_with = "deserialize_section")]
+    pub memory: Option<Threshold>,
+    /// Hottest hwmon sensor, in degrees Celsius.
+    #[serde(deserialize_with = "deserialize_section")]
+    pub temperature: Option<Threshold>,
+}
+
+/// Reads a monitor section, where `enabled = false` turns the monitor off.
+fn deserialize_section<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let mut table = toml::Table::deserialize(deserializer)?;
+    match table.remove("enabled") {
+        None | Some(toml::Value::Boolean(true)) => {}
+        Some(toml::Value::Boolean(false)) => return Ok(None),
+        Some(other) => {
+            return Err(D::Error::custom(format!(
+                "invalid `enabled = {other}`, expected true or false"
+            )));
+        }
+    }
+    T::deserialize(table).map(Some).map_err(D::Error::custom)
+}
+
+impl Default for MonitorConfig {
+    fn default() -> MonitorConfig {
+        MonitorConfig {
+            interval: Duration::from_secs(30),
+            root: PathBuf::from("/"),
+            battery: Some(Threshold {
+                warn: 15.0,
+                critical: Some(5.0),
+                hysteresis: 3.0,
+            }),
+            disk: Some(DiskThreshold {
+                paths: default_disk_paths(),
+                warn: 90.0,
+                critical: Some(97.0),
+                hysteresis: 2.0,
+            }),
+            memory: Some(Threshold {
+                warn: 90.0,
+                critical: Some(97.0),
+                hysteresis: 3.0,
+            }),
+            temperature: Some(Threshold {
+                warn: 85.0,
+                critical: Some(95.0),
+                hysteresis: 5.0,
+            }),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Threshold {
+    pub warn: f64,
+    pub critical: Option<f64>,
+    #[serde(default = "default_hysteresis")]
+    pub hysteresis: f64,
+}
+
+fn default_hysteresis() -> f64 {
+    2.0
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DiskThreshold {
+    #[serde(default = "default_disk_paths")]
+    pub paths: Vec<PathBuf>,
+    pub warn: f64,
+    pub critical: Option<f64>,
+    #[serde(default = "default_hysteresis")]
+    pub hysteresis: f64,
+}
+
+impl DiskThreshold {
+    fn threshold(&self) -> Threshold {
+        Threshold {
+            warn: self.warn,
+            critical: self.critical,
+            hysteresis: self.hysteresis,
+        }
+    }
+}
+
+fn default_disk_paths() -> Vec<PathBuf> {
+    vec![PathBuf::from("/")]
+}
+
+#[derive(Args, Debug)]
+pub struct MonitorSystemArgs {
+    /// Resolve `/sys`, `/proc` and disk paths under this directory instead of the config's
+    #[arg(long)]
+    pub root: Option<PathBuf>,
+
+    /// Check once and exit instead of polling
+    #[arg(long)]
+    pub once: bool,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+enum Level {
+    Ok,
+    Warn,
+    Critical,
+}
+
+impl Threshold {
+    /// The level of `value`, where `higher_is_worse` says which way the threshold faces
+    /// and `margin` pushes the value towards healthy.
+    fn level(&self, value: f64, higher_is_worse: bool, margin: f64) -> Level {
+        let beyond = |limit: f64| {
+            if higher_is_worse {
+                value - margin >= limit
+            } else {
+                value + margin <= limit
+            }
+        };
+        if self.critical.is_some_and(beyond) {
+            Level::Critical
+        } else if beyond(self.warn) {
+            Level::Warn
+        } else {
+            Level::Ok
+        }
+    }
+}
+
+/// Remembers the alert level of each monitored value between polls.
+#[derive(Default)]
+struct Levels {
+    current: HashMap<String, Level>,
+}
+
+impl Levels {
+    /// Records `value`, returning the new level if it got worse.
+    fn update(
+        &mut self,
+        key: &str,
+        value: f64,
+        threshold: &Threshold,
+        higher_is_worse: bool,
+    ) -> Option<Level> {
+        let current = self.current.get(key).copied().unwrap_or(Level::Ok);
+        let level = threshold.level(value, higher_is_worse, 0.0);
+        if level > current {
+            self.current.insert(key.to_string(), level);
+            return Some(level);
+        }
+        // Only come down once clear of the threshold by the hysteresis margin.
+        let recovered = threshold.level(value, higher_is_worse, -threshold.hysteresis);
+        if recovered < current {
+            self.current.insert(key.to_string(), recovered);
+        }
+        None
+    }
+
+    fn reset(&mut self, key: &str) {
+        self.current.remove(key);
+    }
+}
+
+/// A threshold crossing to notify about.
+struct Alert {
+    title: String,
+    body: String,
+    icon: &'static str,
+    level: Level,
+}
+
+fn status_icon(name: &str) -> &'static str {
+    find_icon(STD_STATUS_ICONS, name).unwrap_or(DEFAULT_ICON)
+}
+
+fn under_root(root: &Path, path: &Path) -> PathBuf {
+    root.join(path.strip_prefix("/").unwrap_or(path))
+}
+
+fn read_trimmed(path: &Path) -> Option<String> {
+    fs::read_to_string(path)
+        .ok()
+        .map(|text| text.trim().to_string())
+}
+
+fn check_battery(root: &Path, threshold: &Threshold, levels: &mut Levels, alerts: &mut Vec<Alert>) {
+    let Ok(supplies) = fs::read_dir(under_root(root, Path::new("/sys/class/power_supply"))) else {
+        return;
+    };
+    for supply in supplies.flatten() {
+        let dir = supply.path();
+        if read_trimmed(&dir.join("type")).as_deref() != Some("Battery") {
+            continue;
+        }
+        let name = supply.file_name().to_string_lossy().into_owned();
+        let key = format!("battery:{name}");
+        let Some(capacity) =
+            read_trimmed(&dir.join("capacity")).and_then(|capacity| capacity.parse().ok())
+        else {
+            continue;
+        };
+        if read_trimmed(&dir.join("status")).as_deref() != Some("Discharging") {
+            levels.reset(&key);
+            continue;
+        }
+
+        if let Some(level) = levels.update(&key, capacity, threshold, false) {
+            alerts.push(Alert {
+                title: if level == Level::Critical {
+                    String::from("Battery critically low")
+                } else {
+                    String::from("Battery low")
+                },
+                body: format!("{name} is at {capacity:.0}%"),
+                icon: status_icon(if level == Level::Critical {
+                    "battery-caution"
+                } else {
+                    "battery-low"
+                }),
+                level,
+            });
+        }
+    }
+}
+
+/// Percentage of the file system holding `path` that is in use, as `df` reports it.
+fn disk_used_percent(path: &Path) -> Option<f64> {
+    let path = CString::new(path.as_os_str().as_bytes()).ok()?;
+    // SAFETY: statvfs is plain old data, and the call only writes to it.
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    // SAFETY: `path` is a valid C string and `stat` a valid statvfs to fill in.
+    if unsafe { libc::statvfs(path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    let used = stat.f_blocks.saturating_sub(stat.f_bfree) as f64;
+    let total = used + stat.f_bavail as f64;
+    (total > 0.0).then(|| used / total * 100.0)
+}
+
+fn check_disks(root: &Path, disk: &DiskThreshold, levels: &mut Levels, alerts: &mut Vec<Alert>) {
+    let threshold = disk.threshold();
+    for path in &disk.paths {
+        let Some(used) = disk_used_percent(&under_root(root, path)) else {
+            continue;
+        };
+        if let Some(level) =
+            levels.update(&format!("disk:{}", path.display()), used, &threshold, true)
+        {
+            alerts.push(Alert {
+                title: format!("Disk almost full: {}", path.display()),
+                body: format!("{used:.0}% used"),
+                icon: status_icon(if level == Level::Critical {
+                    "dialog-error"
+                } else {
+                    "dialog-warning"
+                }),
+                level,
+            });
+        }
+    }
+}
+
+/// Memory in use as a percentage, from `MemTotal` and `MemAvailable` in `/proc/meminfo`.
+fn memory_used_percent(root: &Path) -> Option<f64> {
+    let meminfo = fs::read_to_string(under_root(root, Path::new("/proc/meminfo"))).ok()?;
+    let field = |name: &str| -> Option<f64> {
+        meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix(name)?.strip_prefix(':'))?
+            .split_whitespace()
+            .next()?
+            .parse()
+            .ok()
+    };
+    let total = field("MemTotal")?;
+    let available = field("MemAvailable")?;
+    (total > 0.0).then(|| (total - available) / total * 100.0)
+}
+
+fn check_memory(root: &Path, threshold: &Threshold, levels: &mut Levels, alerts: &mut Vec<Alert>) {
+    let Some(used) = memory_used_percent(root) else {
+        return;
+    };
+    if let Some(level) = levels.update("memory", used, threshold, true) {
+        alerts.push(Alert {
+            title: String::from("Memory almost full"),
+            body: format!("{used:.0}% used"),
+            icon: status_icon(if level == Level::Critical {
+                "dialog-error"
+            } else {
+                "dialog-warning"
+            }),
+            level,
+        });
+    }
+}
+
+/// The hottest `temp*_input` reading under `/sys/class/hwmon`, with its chip name.
+fn hottest_sensor(root: &Path) -> Option<(String, f64)> {
+    let chips = fs::read_dir(under_root(root, Path::new("/sys/class/hwmon"))).ok()?;
+    let mut hottest: Option<(String, f64)> = None;
+    for chip in chips.flatten() {
+        let dir = chip.path();
+        let name = read_trimmed(&dir.join("name"))
+            .unwrap_or_else(|| chip.file_name().to_string_lossy().into_owned());
+        let Ok(entries) = fs::read_dir(&dir) else {
+            continue;
+        };
+        for entry in entries.flatten() {
+            let file_name = entry.file_name();
+            let file_name = file_name.to_string_lossy();
+            if !(file_name.starts_with("temp") && file_name.ends_with("_input")) {
+                continue;
+            }
+            // Readings are in millidegrees Celsius.
+            let Some(celsius) = read_trimmed(&entry.path())
+                .and_then(|value| value.parse::<f64>().ok())
+                .map(|millidegrees| millidegrees / 1000.0)
+            else {
+                continue;
+            };
+            if hottest.as_ref().is_none_or(|(_, max)| celsius > *max) {
+                hottest = Some((name.clone(), celsius));
+            }
+        }
+    }
+    hottest
+}
+
+fn check_temperature(
+    root: &Path,
+    threshold: &Threshold,
+    levels: &mut Levels,
+    alerts: &mut Vec<Alert>,
+) {
+    let Some((sensor, celsius)) = hottest_sensor(root) else {
+        return;
+    };
+    if let Some(level) = levels.update("temperature", celsius, threshold, true) {
+        alerts.push(Alert {
+            title: String::from("System running hot"),
+            body: format!("{sensor} is at {celsius:.0}°C"),
+            icon: status_icon(if level == Level::Critical {
+                "dialog-error"
+            } else {
+                "dialog-warning"
+            }),
+            level,
+        });
+    }
+}
+
+fn check_all(monitor: &MonitorConfig, root: &Path, levels: &mut Levels) -> Vec<Alert> {
+    let mut alerts = Vec::new();
+    if let Some(threshold) = &monitor.battery {
+        check_battery(root, threshold, levels, &mut alerts);
+    }
+    if let Some(disk) = &monitor.disk {
+        check_disks(root, disk, levels, &mut alerts);
+    }
+    if let Some(threshold) = &monitor.memory {
+        check_memory(root, threshold, levels, &mut alerts);
+    }
+    if let Some(threshold) = &monitor.temperature {
+        check_temperature(root, threshold, levels, &mut alerts);
+    }
+    alerts
+}
+
+/// Polls battery, disk, memory and temperature and notifies when a threshold is crossed.
+pub async fn handle_monitor_system(
+    args: MonitorSystemArgs,
+    defaults: &NotificationDefaults,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let monitor = &config.monitor;
+    let root = args.root.unwrap_or_else(|| monitor.root.clone());
+
+    let mut sender = Sender::default();
+    let throttle = Throttle::default();
+
+    let mut levels = Levels::default();
+    loop {
+        for alert in check_all(monitor, &root, &mut levels) {
+            let mut notification = NotificationDefaults {
+                app_name: Some(
+                    defaults
+                        .app_name
+                        .clone()
+                        .unwrap_or_else(|| String::from("alertify")),
+                ),
+                ..defaults.clone()
+            }
+            .to_notification(alert.title, alert.body);
+            notification.icon = String::from(alert.icon);
+            if alert.level == Level::Critical {
+                notification.urgency = Urgency::Critical;
+            }
+            notification.actions.clear();
+
+            println!("{}: {}", notification.title, notification.body);
+            if let Err(e) = sender
+                .send(&notification, &config.quiet_hours, &throttle)
+                .await
+            {
+                eprintln!("alertify: failed to send notification: {e}");
+            }
+        }
+
+        if args.once {
+            return Ok(());
+        }
+        tokio::time::sleep(monitor.interval).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_support::FakeRoot;
+
+    fn battery(root: &FakeRoot, capacity: u32, status: &str) {
+        root.write("/sys/class/power_supply/BAT0/type", "Battery\n");
+        root.write(
+            "/sys/class/power_supply/BAT0/capacity",
+            format!("{capacity}\n"),
+        );
+        root.write("/sys/class/power_supply/BAT0/status", format!("{status}\n"));
+    }
+
+    fn monitor(toml: &str) -> MonitorConfig {
+        toml::from_str(toml).unwrap()
+    }
+
+    /// The titles of the alerts one poll raises.
+    fn poll(monitor: &MonitorConfig, root: &FakeRoot, levels: &mut Levels) -> Vec<String> {
+        check_all(monitor, root.path(), levels)
+            .into_iter()
+            .map(|alert| alert.title)
+            .collect()
+    }
+
+    #[test]
+    fn battery_alerts_once_per_crossing_with_hysteresis() {
+        let root = FakeRoot::new("battery");
+        let monitor = monitor(
+            "[battery]\nwarn = 15\ncritical = 5\nhysteresis = 3\n\
+             [disk]\nenabled = false\n[memory]\nenabled = false\n[temperature]\nenabled = false",
+        );
+        let mut levels = Levels::default();
+        let mut at = |capacity, status| {
+            battery(&root, capacity, status);
+            poll(&monitor, &root, &mut levels)
+        };
+
+        assert!(at(50, "Discharging").is_empty());
+        assert_eq!(at(15, "Discharging"), ["Battery low"]);
+        assert!(at(14, "Discharging").is_empty());
+        // Back above the threshold, but not by more than the hysteresis margin.
+        assert!(at(18, "Discharging").is_empty());
+        assert!(at(14, "Discharging").is_empty());
+        // Recovered, so the next crossing alerts again.
+        assert!(at(19, "Discharging").is_empty());
+        assert_eq!(at(15, "Discharging"), ["Battery low"]);
+        assert_eq!(at(5, "Discharging"), ["Battery critically low"]);
+        assert!(at(3, "Discharging").is_empty());
+        // Plugging in resets it.
+        assert!(at(3, "Charging").is_empty());
+        assert_eq!(at(3, "Discharging"), ["Battery critically low"]);
+    }
+
+    #[test]
+    fn memory_and_temperature_come_from_the_fake_tree() {
+        let root = FakeRoot::new("memory");
+        root.write(
+            "/proc/meminfo",
+            "MemTotal:       1000 kB\nMemFree:          10 kB\nMemAvailable:     50 kB\n",
+        );
+        root.write("/sys/class/hwmon/hwmon0/name", "coretemp\n");
+        root.write("/sys/class/hwmon/hwmon0/temp1_input", "70000\n");
+        root.write("/sys/class/hwmon/hwmon0/temp2_input", "91000\n");
+        root.write("/sys/class/hwmon/hwmon1/temp1_input", "40000\n");
+        let monitor = monitor("[battery]\nenabled = false\n[disk]\nenabled = false");
+
+        let mut levels = Levels::default();
+        let alerts = check_all(&monitor, root.path(), &mut levels);
+        let alerts: Vec<(&str, &str, Level)> = alerts
+            .iter()
+            .map(|alert| (alert.title.as_str(), alert.body.as_str(), alert.level))
+            .collect();
+        assert_eq!(
+            alerts,
+            [
+                ("Memory almost full", "95% used", Level::Warn),
+                ("System running hot", "coretemp is at 91°C", Level::Warn),
+            ]
+        );
+
+        root.write("/sys/class/hwmon/hwmon0/temp2_input", "96000\n");
+        assert_eq!(poll(&monitor, &root, &mut levels), ["System running hot"]);
+    }
+
+    #[test]
+    fn sections_default_on_and_can_be_turned_off() {
+        let config = monitor("interval = \"1m\"\n[memory]\nwarn = 80");
+        assert_eq!(config.interval, Duration::from_secs(60));
+        assert!(config.battery.is_some() && config.disk.is_some());
+        let memory = config.memory.unwrap();
+        assert_eq!((memory.warn, memory.critical), (80.0, None));
+
+        let config = monitor("[battery]\nenabled = false\n[disk]\nenabled = true\nwarn = 50");
+        assert!(config.battery.is_none());
+        assert_eq!(config.disk.unwrap().warn, 50.0);
+
+        assert!(toml::from_str::<MonitorConfig>("[battery]\nenabled = \"no\"").is_err());
+        assert!(toml::from_str::<MonitorConfig>("[battery]\nwarn = 10\nlevel = 3").is_err());
+    }
+}
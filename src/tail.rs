@@ -0,0 +1,417 @@
+use crate::config::{Config, NotificationDefaults};
+use crate::deliver::Sender;
+use crate::duration::parse_timeout;
+use crate::notification::{Notification, Urgency};
+use crate::throttle::{RateLimit, Throttle, parse_rate_limit};
+use clap::Args;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Deserializer};
+use std::error::Error;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How often the file is checked for new lines and rotation.
+const POLL_INTERVAL: Duration = Duration::from_millis(250);
+
+/// Fields a rule may set, in `--rule` order.
+const RULE_FIELDS: [&str; 6] = ["title", "body", "icon", "urgency", "app_name", "timeout"];
+
+/// A pattern and the notification to send for lines matching it.
+///
+/// `title` and `body` may refer to capture groups as `$1` or `${name}`; `$0` is the whole
+/// match. The body defaults to the matching line.
+///
+/// ```toml
+/// [[tail_rule]]
+/// pattern = "thread '(.*)' panicked at (.*)"
+/// title = "Panic in $1"
+/// body = "$2"
+/// urgency = "critical"
+/// ```
+#[derive(Clone, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TailRule {
+    #[serde(deserialize_with = "deserialize_regex")]
+    pub pattern: Regex,
+    pub title: Option<String>,
+    pub body: Option<String>,
+    pub icon: Option<String>,
+    pub urgency: Option<Urgency>,
+    pub app_name: Option<String>,
+    #[serde(default, deserialize_with = "crate::duration::deserialize_timeout")]
+    pub timeout: Option<i32>,
+}
+
+fn deserialize_regex<'de, D>(deserializer: D) -> Result<Regex, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    let pattern = String::deserialize(deserializer)?;
+    Regex::new(&pattern).map_err(serde::de::Error::custom)
+}
+
+/// Whether `fields` starts with a field name and its colon.
+fn starts_with_field(fields: &str) -> bool {
+    fields
+        .split_once(':')
+        .is_some_and(|(key, _)| RULE_FIELDS.contains(&key.trim()))
+}
+
+/// Parses a rule written as `PATTERN=>field:value,field:value`, e.g.
+/// `ERROR (.*)=>title:Error,body:$1,urgency:critical`. With nothing after `=>`, the
+/// default title and body are used.
+///
+/// The pattern ends at the first `=>` followed by a field name, so either side may contain
+/// `=>` too. A comma that isn't followed by a field name is part of the value.
+pub fn parse_rule(s: &str) -> Result<TailRule, String> {
+    let (pattern, fields) = s
+        .match_indices("=>")
+        .map(|(index, _)| (&s[..index], &s[index + 2..]))
+        .find(|(_, fields)| fields.trim().is_empty() || starts_with_field(fields))
+        .ok_or_else(|| format!("invalid rule `{s}`, expected PATTERN=>title:...,body:..."))?;
+    let pattern = Regex::new(pattern).map_err(|e| format!("invalid pattern in rule `{s}`: {e}"))?;
+
+    let mut values: Vec<(&str, String)> = Vec::new();
+    // Nothing after `=>` keeps the defaults.
+    let parts = fields.split(',').filter(|_| !fields.trim().is_empty());
+    for part in parts {
+        match part.split_once(':') {
+            Some((key, value)) if RULE_FIELDS.contains(&key.trim()) => {
+                values.push((key.trim(), value.to_string()));
+            }
+            _ => match values.last_mut() {
+                Some((_, value)) => {
+                    value.push(',');
+                    value.push_str(part);
+                }
+                None => {
+                    return Err(format!(
+                        "unknown field in rule `{s}`, expected one of {}",
+                        RULE_FIELDS.join(", ")
+                    ));
+                }
+            },
+        }
+    }
+
+    let mut rule = TailRule {
+        pattern,
+        title: None,
+        body: None,
+        icon: None,
+        urgency: None,
+        app_name: None,
+        timeout: None,
+    };
+    for (key, value) in values {
+        match key {
+            "title" if value.trim().is_empty() => {
+                return Err(format!("empty title in rule `{s}`"));
+            }
+            "title" => rule.title = Some(value),
+            "body" => rule.body = Some(value),
+            "icon" => rule.icon = Some(value),
+            "urgency" => {
+                rule.urgency = Some(
+                    <Urgency as clap::ValueEnum>::from_str(&value, true)
+                        .map_err(|_| format!("invalid urgency `{value}` in rule `{s}`"))?,
+                );
+            }
+            "app_name" => rule.app_name = Some(value),
+            "timeout" => rule.timeout = Some(parse_timeout(&value)?),
+            _ => unreachable!("checked against RULE_FIELDS"),
+        }
+    }
+    Ok(rule)
+}
+
+impl TailRule {
+    fn to_notification(
+        &self,
+        captures: &Captures,
+        line: &str,
+        file: &Path,
+        defaults: &NotificationDefaults,
+    ) -> Notification {
+        let expand = |template: &str| {
+            let mut text = String::new();
+            captures.expand(template, &mut text);
+            text
+        };
+        let title = match &self.title {
+            Some(title) => expand(title),
+            None => format!(
+                "Match in {}",
+                file.file_name().unwrap_or_default().to_string_lossy()
+            ),
+        };
+        let body = match &self.body {
+            Some(body) => expand(body),
+            None => line.to_string(),
+        };
+
+        let mut notification = NotificationDefaults {
+            app_name: self.app_name.clone(),
+            icon: self.icon.clone(),
+            timeout: self.timeout,
+            urgency: self.urgency,
+        }
+        .or(defaults.clone())
+        .to_notification(title, body);
+        notification.actions.clear();
+        notification
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct TailArgs {
+    /// Log file to follow
+    pub file: PathBuf,
+
+    /// Rule as PATTERN=>title:...,body:...,urgency:..., on top of `[[tail_rule]]` in the config
+    #[arg(short, long = "rule", value_parser = parse_rule)]
+    pub rules: Vec<TailRule>,
+
+    /// Ignore the `[[tail_rule]]` entries in the config file
+    #[arg(long)]
+    pub no_config_rules: bool,
+
+    /// Read the file from the beginning instead of only new lines
+    #[arg(long)]
+    pub from_start: bool,
+
+    /// Send at most MAX notifications per DURATION, e.g. 5/1m, collapsing the rest
+    #[arg(long, value_parser = parse_rate_limit)]
+    pub rate_limit: Option<RateLimit>,
+}
+
+/// The log file as currently opened, which rotation may replace under the same path.
+struct Follower {
+    path: PathBuf,
+    reader: Option<BufReader<File>>,
+    inode: u64,
+    position: u64,
+    /// A line still being written, kept until its newline arrives.
+    partial: Vec<u8>,
+}
+
+impl Follower {
+    fn open(&mut self, from_start: bool) -> std::io::Result<bool> {
+        let mut file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        self.inode = file.metadata()?.ino();
+        self.position = if from_start {
+            0
+        } else {
+            file.seek(SeekFrom::End(0))?
+        };
+        file.seek(SeekFrom::Start(self.position))?;
+        self.reader = Some(BufReader::new(file));
+        self.partial.clear();
+        Ok(true)
+    }
+
+    /// Reads the complete lines written since the last call.
+    fn read_lines(&mut self) -> std::io::Result<Vec<String>> {
+        let mut lines = Vec::new();
+        let Some(reader) = self.reader.as_mut() else {
+            return Ok(lines);
+        };
+        loop {
+            let read = reader.read_until(b'\n', &mut self.partial)?;
+            if read == 0 {
+                break;
+            }
+            self.position += read as u64;
+            if self.partial.ends_with(b"\n") {
+                let line = std::mem::take(&mut self.partial);
+                let line = String::from_utf8_lossy(&line);
+                lines.push(line.trim_end_matches(['\n', '\r']).to_string());
+            }
+        }
+        Ok(lines)
+    }
+
+    /// Reopens the file after it was rotated away or truncated, returning whether it did.
+    ///
+    /// Call it once the current file has been drained; the new one is read from the top.
+    fn check_rotation(&mut self) -> std::io::Result<bool> {
+        match fs::metadata(&self.path) {
+            Ok(meta) if self.reader.is_none() || meta.ino() != self.inode => self.open(true),
+            Ok(meta) if meta.len() < self.position => {
+                if let Some(reader) = self.reader.as_mut() {
+                    reader.seek(SeekFrom::Start(0))?;
+                }
+                self.position = 0;
+                self.partial.clear();
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+}
+
+/// Follows the file and sends a notification for each line matching a rule.
+pub async fn handle_tail(
+    args: TailArgs,
+    defaults: &NotificationDefaults,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let mut rules = args.rules;
+    if !args.no_config_rules {
+        rules.extend(config.tail_rule.iter().cloned());
+    }
+    if rules.is_empty() {
+        return Err("no rules given, pass --rule or add [[tail_rule]] to the config".into());
+    }
+
+    let mut follower = Follower {
+        path: args.file.clone(),
+        reader: None,
+        inode: 0,
+        position: 0,
+        partial: Vec::new(),
+    };
+    if !follower.open(args.from_start)? {
+        eprintln!(
+            "alertify: {} does not exist yet, waiting for it",
+            args.file.display()
+        );
+    }
+
+    let mut sender = Sender::default();
+    loop {
+        let mut lines = follower.read_lines()?;
+        if follower.check_rotation()? {
+            lines.extend(follower.read_lines()?);
+        }
+
+        for line in lines {
+            let Some((rule, captures)) = rules.iter().find_map(|rule| {
+                rule.pattern
+                    .captures(&line)
+                    .map(|captures| (rule, captures))
+            }) else {
+                continue;
+            };
+
+            let notification = rule.to_notification(&captures, &line, &args.file, defaults);
+            let throttle = Throttle {
+                rate_limit: args
+                    .rate_limit
+                    .or_else(|| config.rate_limit.get(&notification.app_name).copied()),
+                ..Throttle::default()
+            };
+            if let Err(e) = sender
+                .send(&notification, &config.quiet_hours, &throttle)
+                .await
+            {
+                eprintln!("alertify: failed to send notification: {e}");
+            }
+        }
+
+        tokio::time::sleep(POLL_INTERVAL).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rules_set_fields_and_keep_commas_in_values() {
+        let rule =
+            parse_rule("ERROR (.*)=>title:Error,body:$1, at least,urgency:critical,timeout:10s")
+                .unwrap();
+        assert_eq!(rule.pattern.as_str(), "ERROR (.*)");
+        assert_eq!(rule.title.as_deref(), Some("Error"));
+        assert_eq!(rule.body.as_deref(), Some("$1, at least"));
+        assert_eq!(rule.urgency, Some(Urgency::Critical));
+        assert_eq!(rule.timeout, Some(10_000));
+    }
+
+    #[test]
+    fn arrows_may_appear_on_either_side() {
+        let rule = parse_rule("a=>b (.*)=>title:$1").unwrap();
+        assert_eq!(rule.pattern.as_str(), "a=>b (.*)");
+        assert_eq!(rule.title.as_deref(), Some("$1"));
+
+        let rule = parse_rule("moved (.*)=>title:Moved,body:$1=>archive").unwrap();
+        assert_eq!(rule.pattern.as_str(), "moved (.*)");
+        assert_eq!(rule.body.as_deref(), Some("$1=>archive"));
+    }
+
+    #[test]
+    fn an_empty_template_keeps_the_defaults() {
+        let rule = parse_rule("panicked=>").unwrap();
+        assert_eq!(rule.pattern.as_str(), "panicked");
+        assert!(rule.title.is_none() && rule.body.is_none());
+
+        let rule = parse_rule("panicked=>body:").unwrap();
+        assert_eq!(rule.body.as_deref(), Some(""));
+        assert!(
+            parse_rule("panicked=>title:")
+                .unwrap_err()
+                .contains("empty title")
+        );
+    }
+
+    #[test]
+    fn bad_rules_are_rejected() {
+        assert!(
+            parse_rule("no arrow")
+                .unwrap_err()
+                .contains("expected PATTERN=>")
+        );
+        assert!(
+            parse_rule("x=>colour:red")
+                .unwrap_err()
+                .contains("expected PATTERN=>")
+        );
+        assert!(
+            parse_rule("(=>title:x")
+                .unwrap_err()
+                .contains("invalid pattern")
+        );
+        assert!(
+            parse_rule("x=>urgency:loud")
+                .unwrap_err()
+                .contains("invalid urgency")
+        );
+        assert!(parse_rule("x=>timeout:soon").is_err());
+    }
+
+    #[test]
+    fn notifications_expand_captures() {
+        let rule =
+            parse_rule("thread '(?<thread>.*)' panicked at (.*)=>title:Panic in ${thread},body:$2")
+                .unwrap();
+        let line = "thread 'main' panicked at src/main.rs:3";
+        let captures = rule.pattern.captures(line).unwrap();
+        let notification = rule.to_notification(
+            &captures,
+            line,
+            Path::new("/var/log/app.log"),
+            &NotificationDefaults::default(),
+        );
+        assert_eq!(notification.title, "Panic in main");
+        assert_eq!(notification.body, "src/main.rs:3");
+
+        let rule = parse_rule("panicked=>").unwrap();
+        let captures = rule.pattern.captures(line).unwrap();
+        let notification = rule.to_notification(
+            &captures,
+            line,
+            Path::new("/var/log/app.log"),
+            &NotificationDefaults::default(),
+        );
+        assert_eq!(notification.title, "Match in app.log");
+        assert_eq!(notification.body, line);
+    }
+}
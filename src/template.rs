@@ -0,0 +1,180 @@
+use crate::paths::config_dir;
+use crate::spec::NotificationSpec;
+use std::collections::{BTreeSet, HashMap};
+use std::error::Error;
+use std::path::PathBuf;
+
+/// Resolves a template name to `<config>/templates/NAME.toml`, or uses it as a path
+/// when it already looks like one.
+fn template_path(name: &str) -> PathBuf {
+    if name.contains('/') || name.ends_with(".toml") {
+        PathBuf::from(name)
+    } else {
+        config_dir().join("templates").join(format!("{name}.toml"))
+    }
+}
+
+/// Substitutes `{{key}}` placeholders, collecting any keys that have no value.
+fn render_str(
+    text: &str,
+    vars: &HashMap<String, String>,
+    missing: &mut BTreeSet<String>,
+) -> String {
+    let mut rendered = String::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        rendered.push_str(&rest[..start]);
+        let key = rest[start + 2..start + end].trim();
+        match vars.get(key) {
+            Some(value) => rendered.push_str(value),
+            None => {
+                missing.insert(key.to_string());
+            }
+        }
+        rest = &rest[start + end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
+
+fn render_value(
+    value: &mut toml::Value,
+    vars: &HashMap<String, String>,
+    missing: &mut BTreeSet<String>,
+) {
+    match value {
+        toml::Value::String(text) => *text = render_str(text, vars, missing),
+        toml::Value::Array(items) => {
+            for item in items {
+                render_value(item, vars, missing);
+            }
+        }
+        toml::Value::Table(table) => {
+            for (_, item) in table.iter_mut() {
+                render_value(item, vars, missing);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// Renders template `source` with `vars`, falling back to the template's own `[vars]` table.
+pub fn render_template(
+    name: &str,
+    source: &str,
+    vars: &[(String, String)],
+) -> Result<NotificationSpec, Box<dyn Error>> {
+    let mut table: toml::Table =
+        toml::from_str(source).map_err(|e| format!("failed to parse template `{name}`: {e}"))?;
+
+    let mut values = HashMap::new();
+    if let Some(defaults) = table.remove("vars") {
+        let toml::Value::Table(defaults) = defaults else {
+            return Err(format!("template `{name}`: `vars` must be a table").into());
+        };
+        for (key, value) in defaults {
+            let value = match value {
+                toml::Value::String(text) => text,
+                other => other.to_string(),
+            };
+            values.insert(key, value);
+        }
+    }
+    values.extend(vars.iter().cloned());
+
+    let mut missing = BTreeSet::new();
+    let mut document = toml::Value::Table(table);
+    render_value(&mut document, &values, &mut missing);
+    if !missing.is_empty() {
+        let missing: Vec<_> = missing.into_iter().collect();
+        return Err(format!(
+            "template `{name}` is missing variables: {} (pass them with --var key=value)",
+            missing.join(", ")
+        )
+        .into());
+    }
+
+    Ok(document
+        .try_into()
+        .map_err(|e| format!("invalid template `{name}`: {e}"))?)
+}
+
+pub fn load_template(
+    name: &str,
+    vars: &[(String, String)],
+) -> Result<NotificationSpec, Box<dyn Error>> {
+    let path = template_path(name);
+    let source = std::fs::read_to_string(&path).map_err(|e| {
+        format!(
+            "failed to read template `{name}` at {}: {e}",
+            path.display()
+        )
+    })?;
+    render_template(name, &source, vars)
+}
+
+pub fn parse_var(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("invalid variable `{s}`, expected key=value")),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vars(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(key, value)| (key.to_string(), value.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn fills_placeholders_from_arguments_over_template_vars() {
+        let source = r#"
+title = "Deploy {{ env }}"
+body = "{{service}} is {{state}}"
+
+[vars]
+env = "staging"
+state = "up"
+"#;
+        let spec = render_template(
+            "deploy",
+            source,
+            &vars(&[("service", "api"), ("env", "prod")]),
+        )
+        .unwrap();
+        assert_eq!(spec.title, "Deploy prod");
+        assert_eq!(spec.body, "api is up");
+    }
+
+    #[test]
+    fn reports_every_missing_variable() {
+        let error = render_template("t", r#"title = "{{a}} {{b}} {{a}}""#, &[])
+            .unwrap_err()
+            .to_string();
+        assert!(error.contains("missing variables: a, b"), "{error}");
+    }
+
+    #[test]
+    fn leaves_unclosed_braces_alone() {
+        let spec = render_template("t", r#"title = "{{ open""#, &[]).unwrap();
+        assert_eq!(spec.title, "{{ open");
+    }
+
+    #[test]
+    fn parses_key_value_variables() {
+        assert_eq!(
+            parse_var("a=b=c"),
+            Ok((String::from("a"), String::from("b=c")))
+        );
+        assert!(parse_var("=x").is_err());
+        assert!(parse_var("novalue").is_err());
+    }
+}
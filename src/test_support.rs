@@ -0,0 +1,36 @@
+//! Fixtures shared by the unit tests.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A scratch directory standing in for `/`, for code that reads `/proc`, `/sys` or `/run`
+/// under a root it is given. Removed on drop.
+pub struct FakeRoot(PathBuf);
+
+impl FakeRoot {
+    /// An empty root, named after the test so tests running in parallel don't share one.
+    pub fn new(name: &str) -> FakeRoot {
+        let root =
+            std::env::temp_dir().join(format!("alertify-root-{}-{name}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        FakeRoot(root)
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.0
+    }
+
+    /// Writes `contents` to the absolute `path` inside the root, creating its parents.
+    pub fn write(&self, path: &str, contents: impl AsRef<[u8]>) {
+        let path = self.0.join(path.trim_start_matches('/'));
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(path, contents).unwrap();
+    }
+}
+
+impl Drop for FakeRoot {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -0,0 +1,314 @@
+use crate::duration::parse_duration;
+use crate::notification::{Notification, NotificationsProxy, notify};
+use crate::paths::state_dir;
+use chrono::{DateTime, Local};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::error::Error;
+use std::fs::{self, File, OpenOptions};
+use std::hash::{DefaultHasher, Hash, Hasher};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::path::PathBuf;
+use std::time::Duration;
+
+/// At most `max` notifications per `per` for one application.
+#[derive(Clone, Copy, Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct RateLimit {
+    pub max: u32,
+    #[serde(deserialize_with = "crate::duration::deserialize")]
+    pub per: Duration,
+}
+
+/// Parses a rate limit written as `MAX/DURATION`, e.g. `5/1m`.
+pub fn parse_rate_limit(s: &str) -> Result<RateLimit, String> {
+    let (max, per) = s
+        .split_once('/')
+        .ok_or_else(|| format!("invalid rate limit `{s}`, expected MAX/DURATION such as 5/1m"))?;
+    let max = max
+        .trim()
+        .parse()
+        .map_err(|_| format!("invalid count `{max}` in rate limit `{s}`"))?;
+    Ok(RateLimit {
+        max,
+        per: parse_duration(per)?,
+    })
+}
+
+/// How notifications should be held back.
+#[derive(Debug, Default)]
+pub struct Throttle {
+    pub dedupe_window: Option<Duration>,
+    pub dedupe_key: Option<String>,
+    pub rate_limit: Option<RateLimit>,
+}
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum Outcome {
+    Sent(u32),
+    /// An identical notification was already sent within the dedupe window.
+    Duplicate {
+        suppressed: u32,
+    },
+    /// The app is over its rate limit, so its last notification was updated instead.
+    Collapsed {
+        id: u32,
+        suppressed: u32,
+    },
+    /// Do-not-disturb is active, so it went to the outbox for the next digest.
+    Deferred,
+    /// No notification server could be reached, so it waits in the spool.
+    Spooled,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct DedupeEntry {
+    sent_at: DateTime<Local>,
+    suppressed: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct RateEntry {
+    window_start: DateTime<Local>,
+    sent: u32,
+    suppressed: u32,
+    last_id: u32,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct ThrottleState {
+    dedupe: HashMap<String, DedupeEntry>,
+    rate: HashMap<String, RateEntry>,
+}
+
+/// Entries older than this, or than the windows in use, are dropped from the store.
+const STALE_AFTER: Duration = Duration::from_secs(24 * 60 * 60);
+
+fn elapsed_since(time: DateTime<Local>) -> Duration {
+    (Local::now() - time).to_std().unwrap_or(Duration::ZERO)
+}
+
+impl Throttle {
+    /// Whether dedupe or rate limiting needs the on-disk store.
+    pub fn is_active(&self) -> bool {
+        self.dedupe_window.is_some() || self.rate_limit.is_some()
+    }
+
+    fn key_for(&self, notification: &Notification) -> String {
+        if let Some(key) = &self.dedupe_key {
+            return format!("key:{key}");
+        }
+        // Only needs to be stable between invocations of the same build.
+        let mut hasher = DefaultHasher::new();
+        (
+            &notification.app_name,
+            &notification.title,
+            &notification.body,
+        )
+            .hash(&mut hasher);
+        format!("hash:{:016x}", hasher.finish())
+    }
+}
+
+fn state_path() -> PathBuf {
+    state_dir().join("throttle.json")
+}
+
+/// The throttle state file, held under an exclusive lock until dropped.
+struct StateFile {
+    file: File,
+    state: ThrottleState,
+}
+
+impl StateFile {
+    /// Opens the store, waiting for its lock on a blocking thread rather than on the
+    /// executor the daemon and service answer requests on.
+    async fn open() -> Result<StateFile, Box<dyn Error>> {
+        let path = state_path();
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)?;
+        let mut file = tokio::task::spawn_blocking(move || file.lock().map(|()| file)).await??;
+
+        let mut source = String::new();
+        file.read_to_string(&mut source)?;
+        // A corrupt store only costs us the throttling history, so start over.
+        let state = serde_json::from_str(&source).unwrap_or_default();
+
+        Ok(StateFile { file, state })
+    }
+
+    fn save(&mut self) -> Result<(), Box<dyn Error>> {
+        let contents = serde_json::to_vec(&self.state)?;
+        self.file.set_len(0)?;
+        self.file.seek(SeekFrom::Start(0))?;
+        self.file.write_all(&contents)?;
+        Ok(())
+    }
+}
+
+/// Sends `notification` unless `throttle` holds it back.
+pub async fn notify_throttled(
+    proxy: &NotificationsProxy<'_>,
+    notification: &Notification,
+    throttle: &Throttle,
+) -> Result<Outcome, Box<dyn Error>> {
+    if !throttle.is_active() {
+        return Ok(Outcome::Sent(notify(proxy, notification).await?));
+    }
+
+    // The store stays unlocked while the server answers, so a slow server doesn't hold
+    // up every other alertify waiting on the lock.
+    let (outgoing, collapsed) = match plan(throttle, notification).await? {
+        Plan::Duplicate { suppressed } => return Ok(Outcome::Duplicate { suppressed }),
+        Plan::Send(outgoing) => (outgoing, None),
+        Plan::Collapse {
+            notification,
+            suppressed,
+        } => (notification, Some(suppressed)),
+    };
+    let id = match notify(proxy, &outgoing).await {
+        Ok(id) => id,
+        Err(e) => {
+            // The send error is the one worth reporting; a stale entry only throttles a
+            // little too eagerly until it expires.
+            if let Err(store_error) =
+                forget_attempt(throttle, notification, collapsed.is_some()).await
+            {
+                eprintln!("alertify: failed to update the throttle state: {store_error}");
+            }
+            return Err(e.into());
+        }
+    };
+    if throttle.rate_limit.is_some() {
+        let mut store = StateFile::open().await?;
+        if let Some(entry) = store.state.rate.get_mut(&notification.app_name) {
+            entry.last_id = id;
+        }
+        store.save()?;
+    }
+
+    Ok(match collapsed {
+        Some(suppressed) => Outcome::Collapsed { id, suppressed },
+        None => Outcome::Sent(id),
+    })
+}
+
+/// What to do with a notification that passed do-not-disturb.
+enum Plan {
+    Duplicate {
+        suppressed: u32,
+    },
+    Send(Notification),
+    /// Replace the app's last notification with this one.
+    Collapse {
+        notification: Notification,
+        suppressed: u32,
+    },
+}
+
+/// Decides under the store's lock whether `notification` goes out, recording it as sent
+/// before it is, so concurrent invocations see it.
+async fn plan(throttle: &Throttle, notification: &Notification) -> Result<Plan, Box<dyn Error>> {
+    let mut store = StateFile::open().await?;
+    let state = &mut store.state;
+
+    let stale_after = throttle
+        .dedupe_window
+        .into_iter()
+        .chain(throttle.rate_limit.map(|limit| limit.per))
+        .fold(STALE_AFTER, Duration::max);
+    state
+        .dedupe
+        .retain(|_, entry| elapsed_since(entry.sent_at) < stale_after);
+    state
+        .rate
+        .retain(|_, entry| elapsed_since(entry.window_start) < stale_after);
+
+    let key = throttle.key_for(notification);
+    if let Some(window) = throttle.dedupe_window
+        && let Some(entry) = state.dedupe.get_mut(&key)
+        && elapsed_since(entry.sent_at) < window
+    {
+        entry.suppressed += 1;
+        let suppressed = entry.suppressed;
+        store.save()?;
+        return Ok(Plan::Duplicate { suppressed });
+    }
+
+    let plan = match throttle.rate_limit {
+        Some(limit) => {
+            let entry = state.rate.entry(notification.app_name.clone()).or_default();
+            if elapsed_since(entry.window_start) >= limit.per {
+                *entry = RateEntry {
+                    window_start: Local::now(),
+                    ..RateEntry::default()
+                };
+            }
+
+            if entry.sent >= limit.max && entry.last_id != 0 {
+                entry.suppressed += 1;
+                let mut collapsed = notification.clone();
+                collapsed.replaces_id = entry.last_id;
+                collapsed.body = format!(
+                    "{}\n\n{} more similar notification{}",
+                    notification.body,
+                    entry.suppressed,
+                    if entry.suppressed == 1 { "" } else { "s" }
+                );
+                Plan::Collapse {
+                    notification: collapsed,
+                    suppressed: entry.suppressed,
+                }
+            } else {
+                entry.sent += 1;
+                Plan::Send(notification.clone())
+            }
+        }
+        None => Plan::Send(notification.clone()),
+    };
+
+    if throttle.dedupe_window.is_some() {
+        state.dedupe.insert(
+            key,
+            DedupeEntry {
+                sent_at: Local::now(),
+                suppressed: 0,
+            },
+        );
+    }
+    store.save()?;
+    Ok(plan)
+}
+
+/// Undoes what `plan` recorded for a notification that then failed to send, so a retry
+/// is neither suppressed as a duplicate nor counted against the rate limit twice.
+async fn forget_attempt(
+    throttle: &Throttle,
+    notification: &Notification,
+    collapsed: bool,
+) -> Result<(), Box<dyn Error>> {
+    if !throttle.is_active() {
+        return Ok(());
+    }
+    let mut store = StateFile::open().await?;
+    if throttle.dedupe_window.is_some() {
+        store.state.dedupe.remove(&throttle.key_for(notification));
+    }
+    if throttle.rate_limit.is_some()
+        && let Some(entry) = store.state.rate.get_mut(&notification.app_name)
+    {
+        if collapsed {
+            entry.suppressed = entry.suppressed.saturating_sub(1);
+        } else {
+            entry.sent = entry.sent.saturating_sub(1);
+        }
+    }
+    store.save()
+}
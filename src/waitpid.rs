@@ -0,0 +1,229 @@
+use crate::config::{Config, NotificationDefaults};
+use crate::duration::format_duration;
+use crate::run::{describe_status, notify_finished};
+use clap::Args;
+use std::error::Error;
+use std::fs;
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+use std::time::Duration;
+use tokio::io::unix::AsyncFd;
+use tokio::task::JoinSet;
+
+/// How often `/proc` is checked when pidfd is unavailable.
+const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+#[derive(Args, Debug)]
+pub struct WaitPidArgs {
+    /// Processes to wait for
+    #[arg(required = true)]
+    pub pids: Vec<i32>,
+
+    /// Notification title instead of the process name
+    #[arg(short, long)]
+    pub title: Option<String>,
+
+    /// Icon instead of one chosen by the exit status
+    #[arg(short, long)]
+    pub icon: Option<String>,
+}
+
+/// The parts of `/proc/PID/stat` we need.
+struct Stat {
+    state: char,
+    /// Clock ticks after boot that the process started, which tells a reused PID apart.
+    start_ticks: u64,
+    /// The raw wait status, only meaningful once the process is a zombie.
+    exit_code: i32,
+}
+
+impl Stat {
+    fn is_dead(&self) -> bool {
+        matches!(self.state, 'Z' | 'X')
+    }
+}
+
+fn read_stat(pid: i32) -> io::Result<Stat> {
+    let stat = fs::read_to_string(format!("/proc/{pid}/stat"))?;
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "unexpected /proc stat format");
+
+    // The command name is in parentheses and may itself contain spaces or parentheses.
+    let rest = &stat[stat.rfind(')').ok_or_else(invalid)? + 1..];
+    let fields: Vec<&str> = rest.split_whitespace().collect();
+    let field = |index: usize| fields.get(index).copied().ok_or_else(invalid);
+
+    Ok(Stat {
+        state: field(0)?.chars().next().ok_or_else(invalid)?,
+        start_ticks: field(19)?.parse().map_err(|_| invalid())?,
+        // Older kernels don't have the exit code field.
+        exit_code: field(49)
+            .ok()
+            .and_then(|code| code.parse().ok())
+            .unwrap_or(0),
+    })
+}
+
+fn uptime() -> io::Result<Duration> {
+    let uptime = fs::read_to_string("/proc/uptime")?;
+    uptime
+        .split_whitespace()
+        .next()
+        .and_then(|seconds| seconds.parse().ok())
+        .map(Duration::from_secs_f64)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "unexpected /proc/uptime"))
+}
+
+fn ticks_to_duration(ticks: u64) -> Duration {
+    // SAFETY: sysconf has no preconditions.
+    let per_second = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    Duration::from_secs_f64(ticks as f64 / per_second.max(1) as f64)
+}
+
+/// A process being watched, described while it is still alive.
+struct Watched {
+    pid: i32,
+    name: String,
+    start_ticks: u64,
+    /// Refers to this process even if the PID gets reused; `None` on kernels without it.
+    pidfd: Option<OwnedFd>,
+}
+
+impl Watched {
+    fn new(pid: i32) -> Result<Watched, Box<dyn Error>> {
+        let no_process = || format!("no process with PID {pid}");
+        // Opened before reading `/proc`, so that if the process is still alive afterwards,
+        // what was read describes it and not a process that reused the PID in between.
+        let pidfd = match pidfd_open(pid) {
+            Ok(pidfd) => Some(pidfd),
+            Err(e) if e.raw_os_error() == Some(libc::ESRCH) => return Err(no_process().into()),
+            Err(_) => None,
+        };
+        let stat = read_stat(pid).map_err(|e| match e.kind() {
+            io::ErrorKind::NotFound => no_process(),
+            _ => format!("failed to read process {pid}: {e}"),
+        })?;
+        if stat.is_dead() || pidfd.as_ref().is_some_and(|pidfd| !is_alive(pidfd)) {
+            return Err(format!("process {pid} has already exited").into());
+        }
+        let name = fs::read_to_string(format!("/proc/{pid}/comm"))
+            .map(|comm| comm.trim_end().to_string())
+            .unwrap_or_else(|_| format!("process {pid}"));
+
+        Ok(Watched {
+            pid,
+            name,
+            start_ticks: stat.start_ticks,
+            pidfd,
+        })
+    }
+
+    /// The raw wait status, if the process is still an unreaped zombie and so has one.
+    fn exit_status(&self) -> Option<ExitStatus> {
+        read_stat(self.pid)
+            .ok()
+            .filter(|stat| stat.is_dead() && stat.start_ticks == self.start_ticks)
+            .map(|stat| ExitStatus::from_raw(stat.exit_code))
+    }
+
+    /// Whether the process is still running, and not replaced by a reused PID.
+    fn is_running(&self) -> bool {
+        read_stat(self.pid)
+            .is_ok_and(|stat| !stat.is_dead() && stat.start_ticks == self.start_ticks)
+    }
+}
+
+fn pidfd_open(pid: i32) -> io::Result<OwnedFd> {
+    // SAFETY: pidfd_open takes a PID and flags and returns a new descriptor or -1.
+    let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+    if fd < 0 {
+        return Err(io::Error::last_os_error());
+    }
+    // SAFETY: the descriptor was just created and nothing else owns it.
+    Ok(unsafe { OwnedFd::from_raw_fd(fd as i32) })
+}
+
+/// Whether the process behind `pidfd` hasn't exited yet.
+fn is_alive(pidfd: &OwnedFd) -> bool {
+    // SAFETY: signal 0 only checks the process; the info pointer may be null.
+    let result = unsafe {
+        libc::syscall(
+            libc::SYS_pidfd_send_signal,
+            pidfd.as_raw_fd(),
+            0,
+            std::ptr::null::<libc::siginfo_t>(),
+            0,
+        )
+    };
+    // EPERM means it exists but belongs to someone else.
+    result == 0 || io::Error::last_os_error().raw_os_error() == Some(libc::EPERM)
+}
+
+/// Waits for the process to exit, returning its wait status when it can still be read.
+///
+/// Only the parent may reap a process, so the status is read from `/proc` while the
+/// process is a zombie and is lost if its parent reaps it first.
+async fn wait_exit(watched: &mut Watched) -> io::Result<Option<ExitStatus>> {
+    match watched.pidfd.take() {
+        Some(pidfd) => {
+            // A pidfd becomes readable once the process has exited.
+            let _ = AsyncFd::new(pidfd)?.readable().await?;
+        }
+        None => {
+            while watched.is_running() {
+                tokio::time::sleep(POLL_INTERVAL).await;
+            }
+        }
+    }
+    Ok(watched.exit_status())
+}
+
+/// Waits for each process to exit and sends a notification for it.
+pub async fn handle_wait_pid(
+    args: WaitPidArgs,
+    defaults: &NotificationDefaults,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let mut waiting = JoinSet::new();
+    for pid in &args.pids {
+        let mut watched = Watched::new(*pid)?;
+        eprintln!("Waiting for {} ({}).", watched.name, watched.pid);
+        waiting.spawn(async move {
+            let status = wait_exit(&mut watched).await;
+            (watched, status)
+        });
+    }
+
+    while let Some(result) = waiting.join_next().await {
+        let (watched, status) = result?;
+        let status = status?;
+
+        let runtime = uptime()?.saturating_sub(ticks_to_duration(watched.start_ticks));
+        let body = match status {
+            Some(status) => format!(
+                "{} after {}",
+                describe_status(status).0,
+                format_duration(runtime)
+            ),
+            None => format!("Ran for {}", format_duration(runtime)),
+        };
+        let title = args
+            .title
+            .clone()
+            .unwrap_or_else(|| format!("{} ({}) exited", watched.name, watched.pid));
+
+        println!("{title}: {body}");
+        notify_finished(
+            title,
+            body,
+            status.map(|status| status.success()),
+            args.icon.clone(),
+            defaults,
+            config,
+        )
+        .await;
+    }
+
+    Ok(())
+}
@@ -0,0 +1,335 @@
+use crate::clock::TimeWindow;
+use crate::config::{Config, NotificationDefaults};
+use crate::deliver::Sender;
+use crate::duration::parse_duration;
+use crate::icons::mime::STD_MIME_TYPE_ICONS;
+use crate::icons::place::STD_PLACE_ICONS;
+use crate::icons::utils::find_icon;
+use crate::notification::{DEFAULT_APP_NAME, DEFAULT_ICON};
+use crate::throttle::{Outcome, Throttle};
+use clap::{Args, ValueEnum};
+use futures_lite::StreamExt;
+use glob::Pattern;
+use inotify::{EventMask, Inotify, WatchMask};
+use std::collections::HashMap;
+use std::error::Error;
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Icons for file extensions, all names from `STD_MIME_TYPE_ICONS`.
+const EXTENSION_ICONS: &[(&[&str], &str)] = &[
+    (
+        &["pdf", "doc", "docx", "odt", "rtf", "epub"],
+        "x-office-document",
+    ),
+    (
+        &["xls", "xlsx", "ods", "csv", "tsv"],
+        "x-office-spreadsheet",
+    ),
+    (&["ppt", "pptx", "odp", "key"], "x-office-presentation"),
+    (&["ics", "ical"], "x-office-calendar"),
+    (&["vcf", "vcard"], "x-office-address-book"),
+    (
+        &[
+            "png", "jpg", "jpeg", "gif", "svg", "webp", "bmp", "tif", "tiff", "heic",
+        ],
+        "image-x-generic",
+    ),
+    (
+        &["mp3", "flac", "ogg", "opus", "wav", "m4a", "aac"],
+        "audio-x-generic",
+    ),
+    (
+        &["mp4", "mkv", "webm", "avi", "mov", "m4v"],
+        "video-x-generic",
+    ),
+    (&["ttf", "otf", "woff", "woff2"], "font-x-generic"),
+    (&["html", "htm", "xhtml"], "text-html"),
+    (
+        &["sh", "bash", "zsh", "py", "pl", "rb", "js"],
+        "text-x-script",
+    ),
+    (
+        &[
+            "zip", "tar", "gz", "tgz", "xz", "bz2", "zst", "7z", "rar", "deb", "rpm", "jar",
+        ],
+        "package-x-generic",
+    ),
+    (&["exe", "appimage", "bin"], "application-x-executable"),
+];
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, ValueEnum)]
+pub enum WatchEvent {
+    Create,
+    Modify,
+    Delete,
+}
+
+impl WatchEvent {
+    fn verb(self) -> &'static str {
+        match self {
+            WatchEvent::Create => "created",
+            WatchEvent::Modify => "modified",
+            WatchEvent::Delete => "deleted",
+        }
+    }
+
+    fn from_mask(mask: EventMask) -> Option<WatchEvent> {
+        if mask.intersects(EventMask::CREATE | EventMask::MOVED_TO) {
+            Some(WatchEvent::Create)
+        } else if mask.contains(EventMask::CLOSE_WRITE) {
+            Some(WatchEvent::Modify)
+        } else if mask
+            .intersects(EventMask::DELETE | EventMask::DELETE_SELF | EventMask::MOVED_FROM)
+        {
+            Some(WatchEvent::Delete)
+        } else {
+            None
+        }
+    }
+
+    /// Combines a burst of events on one path into what it amounts to, where `None`
+    /// means nothing happened, e.g. a temporary file that came and went.
+    fn merge(self, next: WatchEvent) -> Option<WatchEvent> {
+        match (self, next) {
+            (WatchEvent::Create, WatchEvent::Modify) => Some(WatchEvent::Create),
+            (WatchEvent::Create, WatchEvent::Delete) => None,
+            // Editors often save by replacing the file.
+            (WatchEvent::Delete, WatchEvent::Create) => Some(WatchEvent::Modify),
+            (_, next) => Some(next),
+        }
+    }
+}
+
+#[derive(Args, Debug)]
+pub struct WatchArgs {
+    /// Directories or files to watch
+    #[arg(required = true)]
+    pub paths: Vec<PathBuf>,
+
+    /// Which changes to notify about
+    #[arg(
+        long,
+        value_enum,
+        value_delimiter = ',',
+        default_value = "create,modify,delete"
+    )]
+    pub on: Vec<WatchEvent>,
+
+    /// Only notify about file names matching this pattern, e.g. '*.pdf'
+    #[arg(short, long, value_parser = Pattern::new)]
+    pub glob: Vec<Pattern>,
+
+    /// Wait for changes to a path to settle for this long before notifying
+    #[arg(short, long, value_parser = parse_duration, default_value = "1s")]
+    pub debounce: Duration,
+}
+
+const WATCH_MASK: WatchMask = WatchMask::CREATE
+    .union(WatchMask::MOVED_TO)
+    .union(WatchMask::CLOSE_WRITE)
+    .union(WatchMask::DELETE)
+    .union(WatchMask::DELETE_SELF)
+    .union(WatchMask::MOVED_FROM);
+
+/// A change waiting for its path to go quiet.
+struct Pending {
+    event: WatchEvent,
+    is_dir: bool,
+    deadline: Instant,
+}
+
+/// Picks an icon from the standard tables for what changed.
+fn icon_for(path: &Path, event: WatchEvent, is_dir: bool) -> &'static str {
+    let name = if event == WatchEvent::Delete {
+        "user-trash"
+    } else if is_dir {
+        "folder"
+    } else {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_ascii_lowercase)
+            .unwrap_or_default();
+        EXTENSION_ICONS
+            .iter()
+            .find(|(extensions, _)| extensions.contains(&extension.as_str()))
+            .map(|(_, icon)| *icon)
+            .unwrap_or_else(|| {
+                let executable = path
+                    .metadata()
+                    .is_ok_and(|meta| meta.permissions().mode() & 0o111 != 0);
+                if executable {
+                    "application-x-executable"
+                } else {
+                    "text-x-generic"
+                }
+            })
+    };
+
+    find_icon(STD_MIME_TYPE_ICONS, name)
+        .or_else(|| find_icon(STD_PLACE_ICONS, name))
+        .unwrap_or(DEFAULT_ICON)
+}
+
+fn matches_glob(path: &Path, globs: &[Pattern]) -> bool {
+    if globs.is_empty() {
+        return true;
+    }
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy())
+        .unwrap_or_default();
+    globs.iter().any(|glob| glob.matches(&name))
+}
+
+async fn notify_change(
+    sender: &mut Sender,
+    path: &Path,
+    pending: &Pending,
+    defaults: &NotificationDefaults,
+    quiet_hours: &[TimeWindow],
+    throttle: &Throttle,
+) {
+    let name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.display().to_string());
+    let mut notification = defaults.to_notification(
+        format!("{name} {}", pending.event.verb()),
+        path.display().to_string(),
+    );
+    if defaults.icon.is_none() {
+        notification.icon = String::from(icon_for(path, pending.event, pending.is_dir));
+    }
+    notification.actions.clear();
+
+    match sender.send(&notification, quiet_hours, throttle).await {
+        Ok(Outcome::Sent(_)) => println!("{}: {}", pending.event.verb(), path.display()),
+        Ok(_) => {}
+        Err(e) => eprintln!("alertify: failed to send notification: {e}"),
+    }
+}
+
+/// Watches the paths with inotify and sends a notification for each settled change.
+pub async fn handle_watch(
+    args: WatchArgs,
+    defaults: &NotificationDefaults,
+    config: &Config,
+) -> Result<(), Box<dyn Error>> {
+    let inotify = Inotify::init()?;
+    let mut watches = inotify.watches();
+    let mut watched = HashMap::new();
+    for path in &args.paths {
+        let descriptor = watches
+            .add(path, WATCH_MASK)
+            .map_err(|e| format!("failed to watch {}: {e}", path.display()))?;
+        watched.insert(descriptor, path.clone());
+    }
+    let mut events = inotify.into_event_stream([0; 4096])?;
+
+    let mut sender = Sender::default();
+    let throttle = Throttle {
+        rate_limit: config
+            .rate_limit
+            .get(defaults.app_name.as_deref().unwrap_or(DEFAULT_APP_NAME))
+            .copied(),
+        ..Throttle::default()
+    };
+
+    let mut pending: HashMap<PathBuf, Pending> = HashMap::new();
+    loop {
+        // Once every watched path is gone, the changes already seen still go out.
+        if watched.is_empty() && pending.is_empty() {
+            return Err("every watched path was removed".into());
+        }
+        let next_deadline = pending.values().map(|change| change.deadline).min();
+        let event = tokio::select! {
+            event = events.next() => event,
+            _ = tokio::time::sleep_until(next_deadline.unwrap_or_else(Instant::now)),
+                if next_deadline.is_some() => {
+                let now = Instant::now();
+                let settled: Vec<PathBuf> = pending
+                    .iter()
+                    .filter(|(_, change)| change.deadline <= now)
+                    .map(|(path, _)| path.clone())
+                    .collect();
+                for path in settled {
+                    if let Some(change) = pending.remove(&path)
+                        && args.on.contains(&change.event)
+                    {
+                        notify_change(
+                            &mut sender,
+                            &path,
+                            &change,
+                            defaults,
+                            &config.quiet_hours,
+                            &throttle,
+                        )
+                        .await;
+                    }
+                }
+                continue;
+            }
+        };
+
+        let Some(event) = event else {
+            return Ok(());
+        };
+        let event = event?;
+        if event.mask.contains(EventMask::Q_OVERFLOW) {
+            eprintln!("alertify: too many changes at once, some were missed");
+            continue;
+        }
+        let (path, kind, is_dir) = if event.mask.contains(EventMask::IGNORED) {
+            let Some(path) = watched.remove(&event.wd) else {
+                continue;
+            };
+            // Saving by renaming a new file over a watched one ends the watch on the old
+            // file, so the new one is watched in its place.
+            match watches.add(&path, WATCH_MASK) {
+                Ok(descriptor) => {
+                    watched.insert(descriptor, path.clone());
+                    let is_dir = path.is_dir();
+                    (path, WatchEvent::Create, is_dir)
+                }
+                Err(e) => {
+                    eprintln!("alertify: stopped watching {}: {e}", path.display());
+                    continue;
+                }
+            }
+        } else {
+            let (Some(root), Some(kind)) =
+                (watched.get(&event.wd), WatchEvent::from_mask(event.mask))
+            else {
+                continue;
+            };
+            let path = match &event.name {
+                Some(name) => root.join(name),
+                None => root.clone(),
+            };
+            (path, kind, event.mask.contains(EventMask::ISDIR))
+        };
+        if !matches_glob(&path, &args.glob) {
+            continue;
+        }
+
+        let deadline = Instant::now() + args.debounce;
+        let event = match pending.remove(&path) {
+            Some(previous) => previous.event.merge(kind),
+            None => Some(kind),
+        };
+        if let Some(event) = event {
+            pending.insert(
+                path,
+                Pending {
+                    event,
+                    is_dir,
+                    deadline,
+                },
+            );
+        }
+    }
+}
@@ -0,0 +1,124 @@
+mod support;
+
+use support::{FakeServer, PrivateBus, TestEnv, run_ok, start_fake_logind};
+
+/// A runtime directory where our own user's `UID/bus` is the test bus.
+fn runtime_dir(env: &TestEnv) -> std::path::PathBuf {
+    let runtime_dir = env.path("run-user");
+    // SAFETY: getuid has no preconditions and can't fail.
+    let uid = unsafe { libc::getuid() };
+    std::fs::create_dir_all(runtime_dir.join(uid.to_string())).unwrap();
+    std::os::unix::fs::symlink(
+        &env.bus.socket,
+        runtime_dir.join(uid.to_string()).join("bus"),
+    )
+    .unwrap();
+    runtime_dir
+}
+
+#[tokio::test]
+async fn broadcast_reports_each_session() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let runtime_dir = runtime_dir(&env);
+    // A socket for a user that doesn't exist, which has to fail on its own.
+    std::fs::create_dir_all(runtime_dir.join("4000000")).unwrap();
+    std::fs::write(runtime_dir.join("4000000").join("bus"), "").unwrap();
+
+    let output = env
+        .alertify([
+            "broadcast",
+            "--source",
+            "runtime-dir",
+            "--title",
+            "Maintenance",
+        ])
+        .arg("--runtime-dir")
+        .arg(&runtime_dir)
+        .env_remove("DBUS_SESSION_BUS_ADDRESS")
+        .output()
+        .await
+        .unwrap();
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(!output.status.success());
+    assert!(stdout.contains("sent (id 1)"), "{stdout}");
+    assert!(
+        stdout.contains("4000000 (4000000): failed: no user with ID 4000000"),
+        "{stdout}"
+    );
+    assert!(stderr.contains("1 of 2 sessions failed"), "{stderr}");
+
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "Maintenance");
+    assert!(calls[0].actions.is_empty());
+}
+
+#[tokio::test]
+async fn broadcast_finds_sessions_through_logind() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let runtime_dir = runtime_dir(&env);
+    let system_bus = PrivateBus::start(&env.path("system-bus"));
+    // SAFETY: getuid has no preconditions and can't fail.
+    let uid = unsafe { libc::getuid() };
+    let _logind = start_fake_logind(&system_bus.address, vec![uid, uid]).await;
+
+    let stdout = run_ok(
+        env.alertify(["broadcast", "--source", "logind", "--urgency", "critical"])
+            .arg("--runtime-dir")
+            .arg(&runtime_dir)
+            .env("DBUS_SYSTEM_BUS_ADDRESS", &system_bus.address),
+    )
+    .await;
+
+    assert_eq!(stdout.lines().count(), 1, "{stdout}");
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].hint::<u8>("urgency"), Some(2));
+}
+
+#[tokio::test]
+async fn broadcast_from_root_sends_as_each_user() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    // SAFETY: getuid has no preconditions and can't fail.
+    if unsafe { libc::getuid() } != 0 {
+        eprintln!("not running as root, skipping the test that switches users");
+        return;
+    }
+    const NOBODY: u32 = 65534;
+    // Test binaries usually live under a home directory other users can't enter.
+    let exe = env.path("alertify");
+    std::fs::copy(env!("CARGO_BIN_EXE_alertify"), &exe).unwrap();
+    let bus = PrivateBus::start_for_all_users(&env.path("shared-bus"));
+    let server = FakeServer::start(&bus.address).await;
+    let runtime_dir = env.path("run-user");
+    std::fs::create_dir_all(runtime_dir.join(NOBODY.to_string())).unwrap();
+    std::os::unix::fs::symlink(
+        &bus.socket,
+        runtime_dir.join(NOBODY.to_string()).join("bus"),
+    )
+    .unwrap();
+
+    let stdout = run_ok(
+        env.alertify_at(
+            &exe,
+            ["broadcast", "--source", "runtime-dir", "--title", "Reboot"],
+        )
+        .arg("--runtime-dir")
+        .arg(&runtime_dir),
+    )
+    .await;
+
+    assert!(
+        stdout.contains(&format!("({NOBODY}): sent (id 1)")),
+        "{stdout}"
+    );
+    let calls = server.wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "Reboot");
+    assert_eq!(calls[0].uid, Some(NOBODY));
+}
@@ -0,0 +1,132 @@
+mod support;
+
+use std::time::{Duration, Instant};
+use support::{EXPIRED_AT_ONCE_SUMMARY, TestEnv};
+
+#[tokio::test]
+async fn daemon_sends_updates_and_reports_events() {
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let socket = env.path("alertify.sock");
+    let mut daemon = env.alertify(["daemon", "--socket"]);
+    let _daemon = daemon.arg(&socket).spawn().unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let stream = loop {
+        match tokio::net::UnixStream::connect(&socket).await {
+            Ok(stream) => break stream,
+            Err(e) => assert!(Instant::now() < deadline, "the daemon never listened: {e}"),
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    };
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut request = async |line: &str| -> serde_json::Value {
+        writer
+            .write_all(format!("{line}\n").as_bytes())
+            .await
+            .unwrap();
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap()
+    };
+
+    let reply = request(r#"{"op": "subscribe"}"#).await;
+    assert_eq!(reply["ok"], true, "{reply}");
+    let reply = request(r#"{"op": "send", "notification": {"title": "build"}}"#).await;
+    assert_eq!(reply["outcome"], "sent", "{reply}");
+    let id = reply["id"].as_u64().unwrap() as u32;
+
+    let reply = request(&format!(
+        r#"{{"op": "update", "id": {id}, "notification": {{"title": "build done"}}}}"#
+    ))
+    .await;
+    assert_eq!(reply["id"], id, "{reply}");
+    let calls = env.server().wait_for_calls(2).await;
+    assert_eq!(calls[1].summary, "build done");
+    assert_eq!(calls[1].replaces_id, id);
+
+    let reply = request(r#"{"op": "list"}"#).await;
+    assert_eq!(reply["notifications"][0]["title"], "build done", "{reply}");
+    let reply = request(r#"{"op": "bogus"}"#).await;
+    assert_eq!(reply["ok"], false, "{reply}");
+
+    env.server().invoke_action(id, "default").await;
+    env.server().close(id, 2).await;
+    let action: serde_json::Value =
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(action["event"], "action", "{action}");
+    assert_eq!(action["action_key"], "default");
+    let closed: serde_json::Value =
+        serde_json::from_str(&lines.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(closed["event"], "closed", "{closed}");
+    assert_eq!(closed["reason_name"], "dismissed");
+}
+
+#[tokio::test]
+async fn daemon_keeps_a_close_that_beats_the_reply() {
+    use std::os::unix::fs::PermissionsExt;
+    use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let socket = env.path("alertify.sock");
+    let mut daemon = env.alertify(["daemon", "--socket"]);
+    let _daemon = daemon.arg(&socket).spawn().unwrap();
+
+    let deadline = Instant::now() + Duration::from_secs(10);
+    let stream = loop {
+        match tokio::net::UnixStream::connect(&socket).await {
+            Ok(stream) => break stream,
+            Err(e) => assert!(Instant::now() < deadline, "the daemon never listened: {e}"),
+        }
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    };
+    let mode = std::fs::metadata(&socket).unwrap().permissions().mode();
+    assert_eq!(mode & 0o777, 0o600);
+
+    let (reader, mut writer) = stream.into_split();
+    let mut lines = BufReader::new(reader).lines();
+    let mut next = async || -> serde_json::Value {
+        let line = tokio::time::timeout(Duration::from_secs(10), lines.next_line())
+            .await
+            .expect("the daemon stopped answering");
+        serde_json::from_str(&line.unwrap().unwrap()).unwrap()
+    };
+    writer
+        .write_all(b"{\"op\": \"subscribe\"}\n")
+        .await
+        .unwrap();
+    assert_eq!(next().await["ok"], true);
+    writer
+        .write_all(
+            format!(
+                "{{\"op\": \"send\", \"notification\": {{\"title\": \"{EXPIRED_AT_ONCE_SUMMARY}\"}}}}\n"
+            )
+            .as_bytes(),
+        )
+        .await
+        .unwrap();
+
+    // The event and the reply race each other, so take them in either order.
+    let (mut reply, mut closed) = (None, None);
+    for _ in 0..2 {
+        let line = next().await;
+        if line["event"].is_null() {
+            reply = Some(line);
+        } else {
+            closed = Some(line);
+        }
+    }
+    let (reply, closed) = (reply.unwrap(), closed.unwrap());
+    assert_eq!(reply["outcome"], "sent", "{reply}");
+    assert_eq!(closed["event"], "closed", "{closed}");
+    assert_eq!(closed["id"], reply["id"]);
+    assert_eq!(closed["reason_name"], "expired");
+
+    writer.write_all(b"{\"op\": \"list\"}\n").await.unwrap();
+    let list = next().await;
+    assert_eq!(list["notifications"], serde_json::json!([]), "{list}");
+}
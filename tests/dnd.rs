@@ -0,0 +1,129 @@
+mod support;
+
+use std::time::{Duration, Instant};
+use support::{TestEnv, run_ok};
+
+#[tokio::test]
+async fn dnd_defers_until_turned_off() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    run_ok(&mut env.alertify(["dnd", "on"])).await;
+    run_ok(&mut env.alertify(["notify", "--title", "first"])).await;
+    run_ok(&mut env.alertify(["notify", "--title", "second"])).await;
+    assert!(env.server().calls().is_empty());
+
+    let stdout = run_ok(&mut env.alertify(["dnd", "off"])).await;
+    assert!(stdout.contains("digest of 2"), "{stdout}");
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls.len(), 1);
+    assert!(calls[0].body.contains("first"), "{}", calls[0].body);
+    assert!(calls[0].body.contains("second"), "{}", calls[0].body);
+}
+
+#[tokio::test]
+async fn critical_notifications_skip_dnd() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    run_ok(&mut env.alertify(["dnd", "on"])).await;
+    run_ok(&mut env.alertify(["notify", "--urgency", "critical"])).await;
+
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].hint::<u8>("urgency"), Some(2));
+}
+
+#[tokio::test]
+async fn pomodoro_notifies_when_done() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    run_ok(&mut env.alertify(["defaults", "--pomodoro", "--length", "1s"])).await;
+
+    let calls = env.server().wait_for_calls(1).await;
+    let call = &calls[0];
+    assert_eq!(call.app_name, "Pomodoro");
+    assert_eq!(call.summary, "Time's up!");
+    assert_eq!(call.timeout, 10_000);
+
+    // The work block turns do-not-disturb on and restores it afterwards.
+    let status = run_ok(&mut env.alertify(["dnd", "status"])).await;
+    assert!(status.contains("Do Not Disturb is off"), "{status}");
+}
+
+#[tokio::test]
+async fn broken_config_does_not_block_dnd_or_history() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    env.write_config("quiet_hours = [");
+    run_ok(&mut env.alertify(["dnd", "on"])).await;
+    let status = run_ok(&mut env.alertify(["dnd", "status"])).await;
+    assert!(status.contains("Do Not Disturb is on"), "{status}");
+    run_ok(&mut env.alertify(["dnd", "off"])).await;
+    run_ok(&mut env.alertify(["history"])).await;
+
+    let output = env.alertify(["notify"]).output().await.unwrap();
+    assert!(!output.status.success());
+}
+
+#[tokio::test]
+async fn killed_pomodoro_releases_dnd_and_the_digest_follows() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let mut pomodoro = env
+        .alertify(["defaults", "--pomodoro", "--length", "1h"])
+        .spawn()
+        .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let status = run_ok(&mut env.alertify(["dnd", "status"])).await;
+        if status.contains("Do Not Disturb is on") {
+            break;
+        }
+        assert!(Instant::now() < deadline, "{status}");
+        tokio::time::sleep(Duration::from_millis(50)).await;
+    }
+    run_ok(&mut env.alertify(["notify", "--title", "held"])).await;
+
+    // SIGKILL, so the guard never gets to turn do-not-disturb off.
+    pomodoro.kill().await.unwrap();
+    let status = run_ok(&mut env.alertify(["dnd", "status"])).await;
+    assert!(status.contains("Do Not Disturb is off"), "{status}");
+
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(
+        calls[0].summary,
+        "1 notification while Do Not Disturb was on"
+    );
+    assert!(calls[0].body.contains("held"), "{}", calls[0].body);
+}
+
+#[tokio::test]
+async fn compat_and_replay_defer_during_dnd() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    run_ok(&mut env.alertify(["notify", "--title", "recorded"])).await;
+    env.server().wait_for_calls(1).await;
+    let history = env.dir.join("state").join("alertify").join("history.jsonl");
+
+    run_ok(&mut env.alertify(["dnd", "on"])).await;
+    run_ok(&mut env.alertify(["compat", "--print-id", "from notify-send"])).await;
+    run_ok(env.alertify(["replay"]).arg(&history)).await;
+    assert!(env.server().calls().is_empty());
+
+    run_ok(&mut env.alertify(["dnd", "off"])).await;
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(
+        calls[0].summary,
+        "2 notifications while Do Not Disturb was on"
+    );
+    assert!(
+        calls[0].body.contains("from notify-send"),
+        "{}",
+        calls[0].body
+    );
+    assert!(calls[0].body.contains("recorded"), "{}", calls[0].body);
+}
@@ -0,0 +1,159 @@
+mod support;
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+use support::{TestEnv, run_ok};
+
+/// Command lines of the schedulers started with `config`, by PID.
+fn schedulers(config: &Path) -> Vec<(i32, Vec<String>)> {
+    let config = config.to_string_lossy();
+    std::fs::read_dir("/proc")
+        .unwrap()
+        .flatten()
+        .filter_map(|entry| {
+            let pid = entry.file_name().to_str()?.parse().ok()?;
+            let cmdline = std::fs::read(entry.path().join("cmdline")).ok()?;
+            let args: Vec<String> = cmdline
+                .split(|byte| *byte == 0)
+                .filter(|arg| !arg.is_empty())
+                .map(|arg| String::from_utf8_lossy(arg).into_owned())
+                .collect();
+            (args.iter().any(|arg| *arg == config)
+                && args.ends_with(&["jobs", "run", "--until-idle"].map(String::from)))
+            .then_some((pid, args))
+        })
+        .collect()
+}
+
+/// Waits for a scheduler started with `config` and returns its command line.
+async fn wait_for_scheduler(config: &Path) -> (i32, Vec<String>) {
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        if let Some(scheduler) = schedulers(config).pop() {
+            return scheduler;
+        }
+        assert!(Instant::now() < deadline, "no scheduler was started");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+fn kill(pid: i32) {
+    // SAFETY: kill has no memory-safety preconditions.
+    unsafe { libc::kill(pid, libc::SIGTERM) };
+}
+
+#[tokio::test]
+async fn in_delivers_through_the_scheduler() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let config = env.path("alertify.toml");
+    std::fs::write(&config, "").unwrap();
+    let stdout = run_ok(
+        env.alertify(["in", "1s", "--title", "Tea"])
+            .arg("--config")
+            .arg(&config),
+    )
+    .await;
+    assert!(stdout.contains("Scheduled #1 \"Tea\""), "{stdout}");
+
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "Tea");
+    for (pid, _) in schedulers(&config) {
+        kill(pid);
+    }
+}
+
+#[tokio::test]
+async fn scheduler_gets_the_config_and_profile_and_only_in_and_at_start_it_early() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let config = env.path("alertify.toml");
+    std::fs::write(&config, "[profile.work]\napp_name = \"Work\"\n").unwrap();
+    let alertify = |args: &[&str]| {
+        let mut command = env.alertify(["--config"]);
+        command.arg(&config).args(["--profile", "work"]).args(args);
+        command
+    };
+
+    run_ok(&mut alertify(&["in", "1h", "--title", "Later"])).await;
+    let (pid, args) = wait_for_scheduler(&config).await;
+    assert!(
+        args.windows(2).any(|pair| pair == ["--profile", "work"]),
+        "{args:?}"
+    );
+    kill(pid);
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while !schedulers(&config).is_empty() {
+        assert!(Instant::now() < deadline, "the scheduler didn't stop");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    // A job is still pending, but until it is due none of these may start a scheduler.
+    run_ok(&mut alertify(&["history"])).await;
+    run_ok(&mut alertify(&["list-icons"])).await;
+    run_ok(&mut alertify(&["jobs", "list"])).await;
+    run_ok(&mut alertify(&["notify"])).await;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    assert!(schedulers(&config).is_empty());
+}
+
+#[tokio::test]
+async fn jobs_that_came_due_without_a_scheduler_are_resumed() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let config = env.path("alertify.toml");
+    std::fs::write(&config, "").unwrap();
+    let alertify = |args: &[&str]| {
+        let mut command = env.alertify(["--config"]);
+        command.arg(&config).args(args);
+        command
+    };
+    let state = env.dir.join("state").join("alertify");
+
+    for resume in [&["jobs", "list"][..], &["notify", "--title", "now"]] {
+        // As after a reboot: the job comes due while no scheduler runs. Holding the lock
+        // keeps `in` from starting one.
+        std::fs::create_dir_all(&state).unwrap();
+        let lock = std::fs::File::create(state.join("scheduler.lock")).unwrap();
+        lock.lock().unwrap();
+        run_ok(&mut alertify(&["in", "1s", "--title", "missed"])).await;
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        drop(lock);
+        assert!(schedulers(&config).is_empty(), "{resume:?}");
+
+        run_ok(&mut alertify(resume)).await;
+        let deadline = Instant::now() + Duration::from_secs(10);
+        let mut summaries = Vec::new();
+        while !summaries.iter().any(|summary| summary == "missed") {
+            assert!(Instant::now() < deadline, "{resume:?}: {summaries:?}");
+            tokio::time::sleep(Duration::from_millis(20)).await;
+            summaries.extend(env.server().calls().into_iter().map(|call| call.summary));
+        }
+    }
+    for (pid, _) in schedulers(&config) {
+        kill(pid);
+    }
+}
+
+#[tokio::test]
+async fn daily_reminders_fire_at_their_anchor_in_the_local_time_zone() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    env.write_config(
+        r#"
+        [[reminder]]
+        title = "Plan the day"
+        schedule = "every 1d"
+        working_hours = { start = "09:00", end = "09:30" }
+        "#,
+    );
+
+    for tz in ["Pacific/Auckland", "America/Los_Angeles", "Asia/Kolkata"] {
+        let stdout = run_ok(env.alertify(["jobs", "list"]).env("TZ", tz)).await;
+        assert!(stdout.contains(" 09:00:00  ("), "{tz}: {stdout}");
+    }
+}
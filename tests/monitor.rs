@@ -0,0 +1,71 @@
+mod support;
+
+use std::process::Stdio;
+use support::{TestEnv, run_ok};
+use tokio::io::{AsyncBufReadExt, BufReader};
+use zbus::connection;
+
+#[tokio::test]
+async fn monitor_prints_the_traffic_as_json_lines() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let mut monitor = env
+        .alertify(["monitor"])
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut events = BufReader::new(monitor.stdout.take().unwrap()).lines();
+    let mut stderr = BufReader::new(monitor.stderr.take().unwrap()).lines();
+    let ready = stderr.next_line().await.unwrap().unwrap();
+    assert!(ready.starts_with("Monitoring"), "{ready}");
+
+    let stdout = run_ok(&mut env.alertify([
+        "notify", "-a", "build", "-u", "critical", "-t", "Build", "-b", "done",
+    ]))
+    .await;
+    let id: u32 = stdout.trim().parse().unwrap();
+    let mut next = async || -> serde_json::Value {
+        serde_json::from_str(&events.next_line().await.unwrap().unwrap()).unwrap()
+    };
+
+    let notify = next().await;
+    assert_eq!(notify["event"], "notify", "{notify}");
+    assert_eq!(notify["id"], id);
+    assert_eq!(notify["app_name"], "build");
+    assert_eq!(notify["title"], "Build");
+    assert_eq!(notify["body"], "done");
+    assert_eq!(notify["hints"]["urgency"], 2);
+    assert!(notify["error"].is_null(), "{notify}");
+
+    let connection = connection::Builder::address(env.bus.address.as_str())
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    connection
+        .call_method(
+            Some("org.freedesktop.Notifications"),
+            "/org/freedesktop/Notifications",
+            Some("org.freedesktop.Notifications"),
+            "CloseNotification",
+            &(id,),
+        )
+        .await
+        .unwrap();
+
+    let requested = next().await;
+    assert_eq!(requested["event"], "close_requested", "{requested}");
+    assert_eq!(requested["id"], id);
+    assert_eq!(
+        requested["sender"],
+        connection.unique_name().unwrap().as_str()
+    );
+
+    let closed = next().await;
+    assert_eq!(closed["event"], "closed", "{closed}");
+    assert_eq!(closed["id"], id);
+    assert_eq!(closed["reason_name"], "closed");
+    assert_eq!(closed["app_name"], "build");
+}
@@ -0,0 +1,435 @@
+mod support;
+
+use support::{TestEnv, run_ok};
+
+#[tokio::test]
+async fn notify_sends_defaults() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let stdout = run_ok(&mut env.alertify(["notify"])).await;
+
+    let calls = env.server().wait_for_calls(1).await;
+    let call = &calls[0];
+    assert_eq!(stdout.trim(), call.id.to_string());
+    assert_eq!(call.app_name, "my_app");
+    assert_eq!(call.summary, "A summary");
+    assert_eq!(call.body, "Some body");
+    assert_eq!(call.icon, "dialog-information");
+    assert_eq!(call.timeout, 5000);
+    assert_eq!(call.hint::<u8>("urgency"), Some(1));
+    assert_eq!(
+        call.hint::<String>("sound-name").as_deref(),
+        Some("message-new-instant")
+    );
+    assert_eq!(
+        call.actions,
+        [
+            "snooze",
+            "Snooze Notification",
+            "dismiss",
+            "Dismiss Notification",
+            "break",
+            "Take a Break",
+            "restart",
+            "Restart Application"
+        ]
+    );
+}
+
+#[tokio::test]
+async fn notify_flags_override_defaults() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    run_ok(&mut env.alertify([
+        "notify",
+        "--app-name",
+        "builds",
+        "--title",
+        "Done",
+        "--body",
+        "All green",
+        "--icon",
+        "emblem-default",
+        "--timeout",
+        "1m",
+        "--urgency",
+        "critical",
+        "--replaces-id",
+        "7",
+    ]))
+    .await;
+
+    let calls = env.server().wait_for_calls(1).await;
+    let call = &calls[0];
+    assert_eq!(call.app_name, "builds");
+    assert_eq!(call.summary, "Done");
+    assert_eq!(call.body, "All green");
+    assert_eq!(call.icon, "emblem-default");
+    assert_eq!(call.timeout, 60_000);
+    assert_eq!(call.replaces_id, 7);
+    assert_eq!(call.hint::<u8>("urgency"), Some(2));
+}
+
+#[tokio::test]
+async fn notify_timeout_keywords() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    run_ok(&mut env.alertify(["notify", "--timeout", "never"])).await;
+    run_ok(&mut env.alertify(["notify", "--timeout", "default"])).await;
+
+    let calls = env.server().wait_for_calls(2).await;
+    assert_eq!(calls[0].timeout, 0);
+    assert_eq!(calls[1].timeout, -1);
+}
+
+#[tokio::test]
+async fn notify_uses_config_profile() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    env.write_config(
+        r#"
+[defaults]
+app_name = "from-config"
+timeout = "12s"
+
+[profile.loud]
+urgency = "critical"
+icon = "dialog-warning"
+"#,
+    );
+    run_ok(&mut env.alertify(["--profile", "loud", "notify"])).await;
+
+    let calls = env.server().wait_for_calls(1).await;
+    let call = &calls[0];
+    assert_eq!(call.app_name, "from-config");
+    assert_eq!(call.timeout, 12_000);
+    assert_eq!(call.icon, "dialog-warning");
+    assert_eq!(call.hint::<u8>("urgency"), Some(2));
+}
+
+#[tokio::test]
+async fn compat_sends_typed_hints() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let stdout = run_ok(&mut env.alertify([
+        "compat",
+        "--print-id",
+        "-u",
+        "low",
+        "-c",
+        "email.arrived",
+        "-e",
+        "-h",
+        "int:value:42",
+        "-h",
+        "string:desktop-entry:mail",
+        "-h",
+        "boolean:resident:true",
+        "-h",
+        "byte:x-level:3",
+        "-h",
+        "double:x-ratio:0.5",
+        "New mail",
+        "From someone",
+    ]))
+    .await;
+
+    let calls = env.server().wait_for_calls(1).await;
+    let call = &calls[0];
+    assert_eq!(stdout.trim(), call.id.to_string());
+    assert_eq!(call.app_name, "notify-send");
+    assert_eq!(call.summary, "New mail");
+    assert_eq!(call.body, "From someone");
+    assert_eq!(call.hint::<u8>("urgency"), Some(0));
+    assert_eq!(
+        call.hint::<String>("category").as_deref(),
+        Some("email.arrived")
+    );
+    assert_eq!(call.hint::<bool>("transient"), Some(true));
+    assert_eq!(call.hint::<i32>("value"), Some(42));
+    assert_eq!(
+        call.hint::<String>("desktop-entry").as_deref(),
+        Some("mail")
+    );
+    assert_eq!(call.hint::<bool>("resident"), Some(true));
+    assert_eq!(call.hint::<u8>("x-level"), Some(3));
+    assert_eq!(call.hint::<f64>("x-ratio"), Some(0.5));
+}
+
+#[tokio::test]
+async fn compat_wait_prints_invoked_action() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let child = env
+        .alertify([
+            "compat",
+            "-A",
+            "open=Open",
+            "-A",
+            "later=Later",
+            "--wait",
+            "Hi",
+        ])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].actions, ["open", "Open", "later", "Later"]);
+    env.server().invoke_action(calls[0].id, "later").await;
+
+    let output = child.wait_with_output().await.unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "later\n");
+}
+
+#[tokio::test]
+async fn compat_wait_returns_when_closed() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let child = env
+        .alertify(["compat", "--wait", "Hi"])
+        .stdout(std::process::Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    let calls = env.server().wait_for_calls(1).await;
+    env.server().close(calls[0].id, 2).await;
+
+    let output = child.wait_with_output().await.unwrap();
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "");
+
+    let history = run_ok(&mut env.alertify(["history", "--json"])).await;
+    assert!(
+        history.contains(r#""close_reason":"dismissed""#),
+        "{history}"
+    );
+}
+
+#[tokio::test]
+async fn send_reads_spec_file() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let spec = env.path("spec.json");
+    std::fs::write(
+        &spec,
+        r#"{"title": "From spec", "body": "Hello", "urgency": "low", "timeout": "2s"}"#,
+    )
+    .unwrap();
+    run_ok(env.alertify(["send", "--from"]).arg(&spec)).await;
+
+    let calls = env.server().wait_for_calls(1).await;
+    let call = &calls[0];
+    assert_eq!(call.summary, "From spec");
+    assert_eq!(call.body, "Hello");
+    assert_eq!(call.timeout, 2000);
+    assert_eq!(call.hint::<u8>("urgency"), Some(0));
+}
+
+#[tokio::test]
+async fn dedupe_window_drops_repeats() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    for _ in 0..3 {
+        run_ok(&mut env.alertify(["notify", "--title", "same", "--dedupe-window", "1m"])).await;
+    }
+    run_ok(&mut env.alertify(["notify", "--title", "different", "--dedupe-window", "1m"])).await;
+
+    let calls = env.server().wait_for_calls(2).await;
+    let titles: Vec<&str> = calls.iter().map(|call| call.summary.as_str()).collect();
+    assert_eq!(titles, ["same", "different"]);
+}
+
+#[tokio::test]
+async fn failed_send_is_not_counted_as_a_duplicate() {
+    let Some(mut env) = TestEnv::without_server() else {
+        return;
+    };
+    let args = ["notify", "--title", "retry", "--dedupe-window", "1m"];
+    let output = env.alertify(args).output().await.unwrap();
+    assert!(!output.status.success());
+
+    env.start_server().await;
+    run_ok(&mut env.alertify(args)).await;
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "retry");
+}
+
+#[tokio::test]
+async fn wait_for_server_sends_once_it_appears() {
+    let Some(mut env) = TestEnv::without_server() else {
+        return;
+    };
+    let child = env
+        .alertify(["--wait-for-server", "10s", "notify", "--title", "late"])
+        .spawn()
+        .unwrap();
+
+    tokio::time::sleep(std::time::Duration::from_millis(300)).await;
+    env.start_server().await;
+
+    let output = child.wait_with_output().await.unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "late");
+}
+
+#[tokio::test]
+async fn wait_for_server_gives_up_with_a_clear_error() {
+    let Some(env) = TestEnv::without_server() else {
+        return;
+    };
+    let output = env
+        .alertify(["--wait-for-server", "300ms", "notify"])
+        .output()
+        .await
+        .unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("no notification server appeared on the session bus within 0.3s"),
+        "{stderr}"
+    );
+}
+
+#[tokio::test]
+async fn wait_for_server_only_holds_up_commands_that_send() {
+    let Some(env) = TestEnv::without_server() else {
+        return;
+    };
+    for args in [
+        &["dnd", "status"][..],
+        &["history"],
+        &["jobs", "list"],
+        &["list-icons"],
+    ] {
+        let started = std::time::Instant::now();
+        run_ok(env.alertify(args).env("ALERTIFY_WAIT_FOR_SERVER", "30s")).await;
+        assert!(
+            started.elapsed() < std::time::Duration::from_secs(10),
+            "{args:?} waited for the server"
+        );
+    }
+}
+
+#[tokio::test]
+async fn bus_address_overrides_the_environment() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    run_ok(
+        env.alertify([
+            "--bus-address",
+            &env.bus.address,
+            "notify",
+            "--title",
+            "direct",
+        ])
+        .env("DBUS_SESSION_BUS_ADDRESS", "unix:path=/nonexistent/bus"),
+    )
+    .await;
+
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "direct");
+}
+
+#[tokio::test]
+async fn rate_limit_collapses_into_the_last_notification() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    for title in ["one", "two", "three"] {
+        run_ok(&mut env.alertify(["notify", "--title", title, "--rate-limit", "1/1m"])).await;
+    }
+
+    let calls = env.server().wait_for_calls(3).await;
+    assert_eq!(calls[0].summary, "one");
+    assert_eq!(calls[1].replaces_id, calls[0].id);
+    assert_eq!(calls[1].body, "Some body\n\n1 more similar notification");
+    assert_eq!(calls[2].replaces_id, calls[0].id);
+    assert_eq!(calls[2].summary, "three");
+    assert_eq!(calls[2].body, "Some body\n\n2 more similar notifications");
+}
+
+#[tokio::test]
+async fn failed_send_is_not_counted_against_the_rate_limit() {
+    let Some(mut env) = TestEnv::without_server() else {
+        return;
+    };
+    let output = env
+        .alertify(["notify", "--title", "lost", "--rate-limit", "2/1m"])
+        .output()
+        .await
+        .unwrap();
+    assert!(!output.status.success());
+
+    env.start_server().await;
+    for title in ["one", "two", "three"] {
+        run_ok(&mut env.alertify(["notify", "--title", title, "--rate-limit", "2/1m"])).await;
+    }
+
+    let calls = env.server().wait_for_calls(3).await;
+    assert_eq!(calls[1].summary, "two");
+    assert_eq!(calls[1].replaces_id, 0);
+    assert_eq!(calls[2].replaces_id, calls[1].id);
+    assert_eq!(calls[2].body, "Some body\n\n1 more similar notification");
+}
+
+#[tokio::test]
+async fn replay_resends_a_recording_with_overrides() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    run_ok(&mut env.alertify(["notify", "--title", "recorded", "--urgency", "low"])).await;
+    env.server().wait_for_calls(1).await;
+
+    let history = env.dir.join("state").join("alertify").join("history.jsonl");
+    run_ok(
+        env.alertify(["replay", "--app-name", "replayed"])
+            .arg(&history),
+    )
+    .await;
+
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "recorded");
+    assert_eq!(calls[0].app_name, "replayed");
+    assert_eq!(calls[0].hint::<u8>("urgency"), Some(0));
+}
+
+#[tokio::test]
+async fn replay_rejects_speeds_it_cannot_wait_for() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    run_ok(&mut env.alertify(["notify", "--title", "one"])).await;
+    run_ok(&mut env.alertify(["notify", "--title", "two"])).await;
+    env.server().wait_for_calls(2).await;
+    let history = env.dir.join("state").join("alertify").join("history.jsonl");
+
+    for (speed, error) in [
+        ("NaN", "--speed must be a number greater than zero"),
+        ("0", "--speed must be a number greater than zero"),
+        ("1e-300", "--speed is too slow"),
+    ] {
+        let output = env
+            .alertify(["replay", "--keep-timing", "--speed", speed])
+            .arg(&history)
+            .output()
+            .await
+            .unwrap();
+        assert!(!output.status.success());
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(stderr.contains(error), "{speed}: {stderr}");
+    }
+}
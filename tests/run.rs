@@ -0,0 +1,91 @@
+mod support;
+
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use support::TestEnv;
+
+#[tokio::test]
+async fn run_passes_the_exit_code_through() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let output = env
+        .alertify(["run", "--", "sh", "-c", "exit 3"])
+        .output()
+        .await
+        .unwrap();
+    assert_eq!(output.status.code(), Some(3));
+
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "sh -c exit 3 failed");
+    assert!(
+        calls[0].body.starts_with("exit status 3 after"),
+        "{}",
+        calls[0].body
+    );
+    assert_eq!(calls[0].icon, "dialog-error");
+    assert_eq!(calls[0].hint::<u8>("urgency"), Some(2));
+
+    let output = env
+        .alertify(["run", "--", "sh", "-c", "kill -9 $$"])
+        .output()
+        .await
+        .unwrap();
+    assert_eq!(output.status.code(), Some(137));
+
+    let output = env
+        .alertify(["run", "--", "alertify-no-such-command"])
+        .output()
+        .await
+        .unwrap();
+    assert_eq!(output.status.code(), Some(127));
+}
+
+#[tokio::test]
+async fn run_puts_the_stderr_tail_in_the_body() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let script = "echo one >&2; echo two >&2; printf '10%%\\r100%%\\n' >&2; echo out";
+    let output = env
+        .alertify([
+            "run", "--lines", "2", "--title", "Build", "--", "sh", "-c", script,
+        ])
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success());
+    // Both streams still reach the terminal.
+    assert!(String::from_utf8_lossy(&output.stdout).contains("out"));
+    assert!(String::from_utf8_lossy(&output.stderr).contains("one\ntwo\n"));
+
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "Build");
+    assert_eq!(calls[0].icon, "emblem-default");
+    assert!(
+        calls[0].body.ends_with("\n\ntwo\n100%"),
+        "{}",
+        calls[0].body
+    );
+}
+
+#[tokio::test]
+async fn run_does_not_wait_for_grandchildren_holding_stderr() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let started = Instant::now();
+    // The grandchild inherits stdout too, so that can't be a pipe we wait on either.
+    let status = env
+        .alertify(["run", "--", "sh", "-c", "sleep 30 & echo started >&2"])
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .await
+        .unwrap();
+    assert!(status.success());
+    assert!(started.elapsed() < Duration::from_secs(10));
+
+    let calls = env.server().wait_for_calls(1).await;
+    assert!(calls[0].body.ends_with("\n\nstarted"), "{}", calls[0].body);
+}
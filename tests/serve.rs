@@ -0,0 +1,63 @@
+mod support;
+
+use std::process::Stdio;
+use support::TestEnv;
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+
+#[tokio::test]
+async fn serve_renders_notifications_and_acts_as_the_user() {
+    let Some(env) = TestEnv::without_server() else {
+        return;
+    };
+    let mut serve = env
+        .alertify(["serve", "--json"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let mut stdin = serve.stdin.take().unwrap();
+    let mut events = BufReader::new(serve.stdout.take().unwrap()).lines();
+    let mut stderr = BufReader::new(serve.stderr.take().unwrap()).lines();
+    let ready = stderr.next_line().await.unwrap().unwrap();
+    assert!(ready.starts_with("Serving"), "{ready}");
+
+    let compat = env
+        .alertify(["compat", "-A", "open=Open", "--wait", "Build", "done"])
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let shown: serde_json::Value =
+        serde_json::from_str(&events.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(shown["event"], "notify", "{shown}");
+    assert_eq!(shown["summary"], "Build");
+    assert_eq!(shown["actions"][0]["key"], "open");
+
+    let id = shown["id"].as_u64().unwrap();
+    stdin
+        .write_all(format!("invoke {id} open\n").as_bytes())
+        .await
+        .unwrap();
+    let output = compat.wait_with_output().await.unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "open\n");
+
+    let action: serde_json::Value =
+        serde_json::from_str(&events.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(action["event"], "action", "{action}");
+    let closed: serde_json::Value =
+        serde_json::from_str(&events.next_line().await.unwrap().unwrap()).unwrap();
+    assert_eq!(closed["reason"], "dismissed", "{closed}");
+}
+
+#[tokio::test]
+async fn serve_refuses_to_take_over_without_replace() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let output = env.alertify(["serve"]).output().await.unwrap();
+
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("pass --replace to take over"), "{stderr}");
+}
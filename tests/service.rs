@@ -0,0 +1,122 @@
+mod support;
+
+use futures_lite::StreamExt;
+use std::time::{Duration, Instant};
+use support::{TestEnv, run_ok};
+use zbus::proxy::SignalStream;
+
+/// The body of the next signal on `stream`, failing after 10 seconds.
+async fn next_signal<T>(stream: &mut SignalStream<'_>) -> T
+where
+    T: serde::de::DeserializeOwned + zbus::zvariant::Type,
+{
+    let signal = tokio::time::timeout(Duration::from_secs(10), stream.next())
+        .await
+        .expect("timed out waiting for a signal")
+        .unwrap();
+    signal.body().deserialize().unwrap()
+}
+
+#[tokio::test]
+async fn service_exposes_dnd_reminders_timers_and_history() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let _service = env.alertify(["service"]).spawn().unwrap();
+    let connection = zbus::connection::Builder::address(env.bus.address.as_str())
+        .unwrap()
+        .build()
+        .await
+        .unwrap();
+    let service = zbus::Proxy::new(
+        &connection,
+        "io.github.alertify",
+        "/io/github/alertify",
+        "io.github.alertify",
+    )
+    .await
+    .unwrap();
+    let deadline = Instant::now() + Duration::from_secs(10);
+    while let Err(e) = service
+        .call::<_, _, Vec<(u32, String, i64)>>("ListTimers", &())
+        .await
+    {
+        assert!(Instant::now() < deadline, "the service never answered: {e}");
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    let mut dnd_changed = service.receive_signal("DoNotDisturbChanged").await.unwrap();
+    let () = service.call("SetDoNotDisturb", &(true,)).await.unwrap();
+    assert!(next_signal::<bool>(&mut dnd_changed).await);
+    let (active, reason): (bool, String) = service.call("DoNotDisturb", &()).await.unwrap();
+    assert!(active);
+    assert_eq!(reason, "manual");
+    // Changes made outside the service are announced too.
+    run_ok(&mut env.alertify(["dnd", "off"])).await;
+    assert!(!next_signal::<bool>(&mut dnd_changed).await);
+
+    let mut reminders_changed = service.receive_signal("RemindersChanged").await.unwrap();
+    let due = chrono::Local::now().timestamp() + 2;
+    let id: u32 = service
+        .call("ScheduleReminder", &("stretch", "", due))
+        .await
+        .unwrap();
+    let () = next_signal(&mut reminders_changed).await;
+    let later: u32 = service
+        .call("ScheduleReminder", &("lunch", "", due + 3600))
+        .await
+        .unwrap();
+    let () = next_signal(&mut reminders_changed).await;
+    run_ok(&mut env.alertify(["jobs", "cancel", &later.to_string()])).await;
+    let () = next_signal(&mut reminders_changed).await;
+    let reminders: Vec<(u32, i64, String)> = service.call("ListReminders", &()).await.unwrap();
+    assert_eq!(reminders, [(id, due, String::from("stretch"))]);
+    // And so is the scheduler delivering it.
+    let () = next_signal(&mut reminders_changed).await;
+    let reminders: Vec<(u32, i64, String)> = service.call("ListReminders", &()).await.unwrap();
+    assert!(reminders.is_empty());
+
+    let mut finished = service.receive_signal("TimerFinished").await.unwrap();
+    let timer: u32 = service.call("StartTimer", &("tea", 0u32)).await.unwrap();
+    let finished_id: u32 = next_signal(&mut finished).await;
+    assert_eq!(finished_id, timer);
+
+    let calls = env.server().wait_for_calls(2).await;
+    let mut titles: Vec<&str> = calls.iter().map(|call| call.summary.as_str()).collect();
+    titles.sort_unstable();
+    assert_eq!(titles, ["stretch", "tea"]);
+
+    let history: Vec<(u32, i64, String, String, String, String, String)> =
+        service.call("QueryHistory", &("", 1u32)).await.unwrap();
+    assert_eq!(history.len(), 1);
+
+    // The service stays connected, so it learns how its timers ended.
+    let tea = calls.iter().find(|call| call.summary == "tea").unwrap();
+    env.server().close(tea.id, 2).await;
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let history: Vec<(u32, i64, String, String, String, String, String)> =
+            service.call("QueryHistory", &("", 0u32)).await.unwrap();
+        let entry = history.iter().find(|entry| entry.3 == "tea").unwrap();
+        if entry.6 == "dismissed" {
+            break;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "the close was not recorded: {entry:?}"
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+
+    // Held back while do-not-disturb is on, and delivered as a digest once it's off.
+    let () = service.call("SetDoNotDisturb", &(true,)).await.unwrap();
+    let held: u32 = service.call("StartTimer", &("held", 0u32)).await.unwrap();
+    assert_eq!(next_signal::<u32>(&mut finished).await, held);
+    let () = service.call("SetDoNotDisturb", &(false,)).await.unwrap();
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(
+        calls[0].summary,
+        "1 notification while Do Not Disturb was on"
+    );
+    assert!(calls[0].body.contains("held"), "{}", calls[0].body);
+}
@@ -0,0 +1,225 @@
+mod support;
+
+use std::io::Write;
+use std::process::Stdio;
+use std::time::{Duration, Instant};
+use support::{REJECTED_SUMMARY, TestEnv, run_ok};
+
+/// Waits until `count` notifications are in the spool.
+async fn wait_for_spooled(env: &TestEnv, count: usize) {
+    let spool = env.path("state").join("alertify").join("spool");
+    let deadline = Instant::now() + Duration::from_secs(10);
+    loop {
+        let spooled = std::fs::read_dir(&spool).map_or(0, |entries| {
+            entries
+                .flatten()
+                .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "json"))
+                .count()
+        });
+        if spooled >= count {
+            return;
+        }
+        assert!(
+            Instant::now() < deadline,
+            "expected {count} spooled, got {spooled}"
+        );
+        tokio::time::sleep(Duration::from_millis(20)).await;
+    }
+}
+
+#[tokio::test]
+async fn spooled_notifications_are_flushed_once_a_server_appears() {
+    let Some(mut env) = TestEnv::without_server() else {
+        return;
+    };
+    env.write_config("spool = true\n");
+    let output = env
+        .alertify(["notify", "--title", "overnight", "--body", "backup done"])
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.contains("spooled"), "{stderr}");
+
+    let output = env.alertify(["flush"]).output().await.unwrap();
+    assert!(!output.status.success());
+
+    env.start_server().await;
+    let stdout = run_ok(&mut env.alertify(["flush"])).await;
+    assert!(
+        stdout.contains("Delivered 1 spooled notification."),
+        "{stdout}"
+    );
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "overnight");
+    assert!(
+        calls[0].body.starts_with("backup done\n\n(sent at "),
+        "{}",
+        calls[0].body
+    );
+
+    let stdout = run_ok(&mut env.alertify(["flush"])).await;
+    assert!(stdout.contains("The spool is empty."), "{stdout}");
+}
+
+#[tokio::test]
+async fn spooled_notifications_go_out_with_the_next_invocation() {
+    let Some(mut env) = TestEnv::without_server() else {
+        return;
+    };
+    env.write_config("spool = true\n");
+    run_ok(&mut env.alertify(["notify", "--title", "first"])).await;
+
+    env.start_server().await;
+    run_ok(&mut env.alertify(["notify", "--title", "second"])).await;
+    let calls = env.server().wait_for_calls(2).await;
+    let titles: Vec<&str> = calls.iter().map(|call| call.summary.as_str()).collect();
+    assert_eq!(titles, ["first", "second"]);
+}
+
+#[tokio::test]
+async fn failed_sends_are_lost_without_the_spool() {
+    let Some(env) = TestEnv::without_server() else {
+        return;
+    };
+    let output = env.alertify(["notify"]).output().await.unwrap();
+    assert!(!output.status.success());
+
+    let stdout = run_ok(&mut env.alertify(["flush"])).await;
+    assert!(stdout.contains("The spool is empty."), "{stdout}");
+}
+
+#[tokio::test]
+async fn send_spools_when_no_server_is_reachable() {
+    let Some(mut env) = TestEnv::without_server() else {
+        return;
+    };
+    env.write_config("spool = true\n");
+    let spec = env.path("spec.json");
+    std::fs::write(&spec, r#"[{"title": "one"}, {"title": "two"}]"#).unwrap();
+    let stdout = run_ok(env.alertify(["send", "--from"]).arg(&spec)).await;
+    assert_eq!(
+        stdout,
+        "[0] spooled (no notification server)\n[1] spooled (no notification server)\n"
+    );
+
+    env.start_server().await;
+    run_ok(&mut env.alertify(["flush"])).await;
+    let calls = env.server().wait_for_calls(2).await;
+    let titles: Vec<&str> = calls.iter().map(|call| call.summary.as_str()).collect();
+    assert_eq!(titles, ["one", "two"]);
+}
+
+#[tokio::test]
+async fn waiting_for_the_server_in_vain_spools() {
+    let Some(env) = TestEnv::without_server() else {
+        return;
+    };
+    env.write_config("spool = true\n");
+    let output = env
+        .alertify(["--wait-for-server", "200ms", "notify", "--title", "late"])
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    assert!(String::from_utf8_lossy(&output.stderr).contains("notification spooled"));
+    wait_for_spooled(&env, 1).await;
+}
+
+#[tokio::test]
+async fn tail_spools_while_no_server_is_reachable() {
+    let Some(mut env) = TestEnv::without_server() else {
+        return;
+    };
+    env.write_config("spool = true\n");
+    let log = env.path("app.log");
+    std::fs::write(&log, "").unwrap();
+    let _tail = env
+        .alertify(["tail", "--rule", "ERROR (.*)=>title:$1"])
+        .arg(&log)
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let mut file = std::fs::OpenOptions::new().append(true).open(&log).unwrap();
+    writeln!(file, "ERROR first").unwrap();
+    wait_for_spooled(&env, 1).await;
+
+    // The spooled one goes out ahead of the next match.
+    env.start_server().await;
+    writeln!(file, "ERROR second").unwrap();
+    let calls = env.server().wait_for_calls(2).await;
+    let titles: Vec<&str> = calls.iter().map(|call| call.summary.as_str()).collect();
+    assert_eq!(titles, ["first", "second"]);
+}
+
+#[tokio::test]
+async fn flush_sets_aside_what_the_server_rejects_and_goes_on() {
+    let Some(mut env) = TestEnv::without_server() else {
+        return;
+    };
+    env.write_config("spool = true\n");
+    for title in [REJECTED_SUMMARY, "accepted"] {
+        run_ok(&mut env.alertify(["notify", "--title", title])).await;
+    }
+
+    env.start_server().await;
+    let output = env.alertify(["flush"]).output().await.unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains("Delivered 1 spooled notification."),
+        "{stdout}"
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("the server rejected"), "{stderr}");
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "accepted");
+
+    let spool = env.path("state").join("alertify").join("spool");
+    let set_aside = std::fs::read_dir(spool)
+        .unwrap()
+        .flatten()
+        .filter(|entry| entry.path().extension().is_some_and(|ext| ext == "bad"))
+        .count();
+    assert_eq!(set_aside, 1);
+    let stdout = run_ok(&mut env.alertify(["flush"])).await;
+    assert!(stdout.contains("The spool is empty."), "{stdout}");
+}
+
+#[tokio::test]
+async fn compat_and_replay_spool_when_no_server_is_reachable() {
+    let Some(mut env) = TestEnv::without_server() else {
+        return;
+    };
+    env.write_config("spool = true\n");
+    let output = env
+        .alertify(["compat", "--print-id", "from notify-send"])
+        .output()
+        .await
+        .unwrap();
+    assert!(output.status.success(), "{output:?}");
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("No notification server is reachable: notification spooled."),
+        "{stderr}"
+    );
+
+    let recording = env.path("recording.jsonl");
+    std::fs::write(
+        &recording,
+        r#"{"event":"sent","timestamp":"2026-01-05T09:00:00+00:00","id":7,"notification":{"app_name":"app","replaces_id":0,"title":"replayed","body":"","icon":"","timeout":-1,"urgency":"normal","category":null,"transient":false,"actions":[],"hints":{}}}"#,
+    )
+    .unwrap();
+    let stdout = run_ok(env.alertify(["replay"]).arg(&recording)).await;
+    assert_eq!(stdout, "#7 spooled (no notification server): replayed\n");
+    wait_for_spooled(&env, 2).await;
+
+    env.start_server().await;
+    run_ok(&mut env.alertify(["flush"])).await;
+    let calls = env.server().wait_for_calls(2).await;
+    let titles: Vec<&str> = calls.iter().map(|call| call.summary.as_str()).collect();
+    assert_eq!(titles, ["from notify-send", "replayed"]);
+}
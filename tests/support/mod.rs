@@ -0,0 +1,454 @@
+//! A private session bus with a fake `org.freedesktop.Notifications` server on it, so the
+//! CLI can be run end to end without a desktop.
+//!
+//! Tests that need a bus skip themselves when `dbus-daemon` isn't installed.
+
+// Each test binary uses only part of this module.
+#![allow(dead_code)]
+
+use std::collections::HashMap;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{Child, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
+use tokio::process::Command;
+use zbus::fdo::DBusProxy;
+use zbus::message::Header;
+use zbus::object_server::SignalEmitter;
+use zbus::zvariant::{OwnedObjectPath, OwnedValue, Value};
+use zbus::{Connection, connection, interface};
+
+const BUS_NAME: &str = "org.freedesktop.Notifications";
+const OBJECT_PATH: &str = "/org/freedesktop/Notifications";
+
+/// The title of notifications the fake server rejects.
+pub const REJECTED_SUMMARY: &str = "rejected by the server";
+
+/// The title of notifications the fake server closes as expired before it even replies.
+pub const EXPIRED_AT_ONCE_SUMMARY: &str = "expired at once";
+
+/// How long `wait_for_calls` waits before giving up.
+const CALL_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Whether `dbus-daemon` can be run, warning once when it can't.
+pub fn have_dbus() -> bool {
+    static AVAILABLE: OnceLock<bool> = OnceLock::new();
+    *AVAILABLE.get_or_init(|| {
+        let available = std::process::Command::new("dbus-daemon")
+            .arg("--version")
+            .stdout(Stdio::null())
+            .stderr(Stdio::null())
+            .status()
+            .is_ok_and(|status| status.success());
+        if !available {
+            eprintln!("dbus-daemon is not installed, skipping the tests that need a bus");
+        }
+        available
+    })
+}
+
+/// A `dbus-daemon` of our own, killed on drop.
+pub struct PrivateBus {
+    daemon: Child,
+    pub address: String,
+    pub socket: PathBuf,
+}
+
+impl PrivateBus {
+    /// Starts a bus listening on the socket at `socket`.
+    pub fn start(socket: &Path) -> PrivateBus {
+        let mut command = std::process::Command::new("dbus-daemon");
+        command
+            .arg("--session")
+            .arg(format!("--address=unix:path={}", socket.display()));
+        PrivateBus::spawn(command, socket)
+    }
+
+    /// Starts a bus that every user may connect to, unlike a session bus.
+    pub fn start_for_all_users(socket: &Path) -> PrivateBus {
+        let config = socket.with_extension("conf");
+        std::fs::write(
+            &config,
+            format!(
+                r#"<!DOCTYPE busconfig PUBLIC "-//freedesktop//DTD D-Bus Bus Configuration 1.0//EN"
+ "http://www.freedesktop.org/standards/dbus/1.0/busconfig.dtd">
+<busconfig>
+  <type>session</type>
+  <listen>unix:path={}</listen>
+  <auth>EXTERNAL</auth>
+  <policy context="default">
+    <allow user="*"/>
+    <allow send_destination="*" eavesdrop="true"/>
+    <allow eavesdrop="true"/>
+    <allow own="*"/>
+  </policy>
+</busconfig>
+"#,
+                socket.display()
+            ),
+        )
+        .unwrap();
+        let mut command = std::process::Command::new("dbus-daemon");
+        command.arg(format!("--config-file={}", config.display()));
+        PrivateBus::spawn(command, socket)
+    }
+
+    fn spawn(mut command: std::process::Command, socket: &Path) -> PrivateBus {
+        let mut daemon = command
+            .args(["--nofork", "--print-address"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("dbus-daemon is needed to run these tests");
+        let mut address = String::new();
+        BufReader::new(daemon.stdout.take().unwrap())
+            .read_line(&mut address)
+            .expect("failed to read the bus address");
+        PrivateBus {
+            daemon,
+            address: address.trim().to_string(),
+            socket: socket.to_path_buf(),
+        }
+    }
+}
+
+impl Drop for PrivateBus {
+    fn drop(&mut self) {
+        let _ = self.daemon.kill();
+        let _ = self.daemon.wait();
+    }
+}
+
+/// A `Notify` call as the server received it.
+#[derive(Debug)]
+pub struct Call {
+    pub id: u32,
+    /// The user ID of the connection that sent it.
+    pub uid: Option<u32>,
+    pub app_name: String,
+    pub replaces_id: u32,
+    pub icon: String,
+    pub summary: String,
+    pub body: String,
+    pub actions: Vec<String>,
+    pub hints: HashMap<String, OwnedValue>,
+    pub timeout: i32,
+}
+
+impl Call {
+    pub fn hint<'a, T>(&'a self, name: &str) -> Option<T>
+    where
+        T: TryFrom<&'a Value<'a>>,
+        <T as TryFrom<&'a Value<'a>>>::Error: Into<zbus::zvariant::Error>,
+    {
+        self.hints.get(name)?.downcast_ref().ok()
+    }
+}
+
+/// Records notifications, rejecting any titled `REJECTED_SUMMARY` the way a server that
+/// refuses one would, and closing any titled `EXPIRED_AT_ONCE_SUMMARY` before replying.
+struct FakeNotifications {
+    calls: Arc<Mutex<Vec<Call>>>,
+    next_id: u32,
+}
+
+#[interface(name = "org.freedesktop.Notifications")]
+impl FakeNotifications {
+    #[allow(clippy::too_many_arguments)]
+    async fn notify(
+        &mut self,
+        #[zbus(header)] header: Header<'_>,
+        #[zbus(connection)] connection: &Connection,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        app_name: String,
+        replaces_id: u32,
+        icon: String,
+        summary: String,
+        body: String,
+        actions: Vec<String>,
+        hints: HashMap<String, OwnedValue>,
+        timeout: i32,
+    ) -> zbus::fdo::Result<u32> {
+        if summary == REJECTED_SUMMARY {
+            return Err(zbus::fdo::Error::InvalidArgs(String::from("rejected")));
+        }
+        let id = if replaces_id != 0 {
+            replaces_id
+        } else {
+            self.next_id += 1;
+            self.next_id
+        };
+        let uid = match (header.sender(), DBusProxy::new(connection).await) {
+            (Some(sender), Ok(dbus)) => dbus
+                .get_connection_unix_user(sender.clone().into())
+                .await
+                .ok(),
+            _ => None,
+        };
+        let expires_at_once = summary == EXPIRED_AT_ONCE_SUMMARY;
+        self.calls.lock().unwrap().push(Call {
+            id,
+            uid,
+            app_name,
+            replaces_id,
+            icon,
+            summary,
+            body,
+            actions,
+            hints,
+            timeout,
+        });
+        if expires_at_once {
+            FakeNotifications::notification_closed(&emitter, id, 1).await?;
+        }
+        Ok(id)
+    }
+
+    async fn close_notification(
+        &self,
+        #[zbus(signal_emitter)] emitter: SignalEmitter<'_>,
+        id: u32,
+    ) -> zbus::fdo::Result<()> {
+        FakeNotifications::notification_closed(&emitter, id, 3).await?;
+        Ok(())
+    }
+
+    fn get_capabilities(&self) -> Vec<String> {
+        vec![String::from("actions"), String::from("body")]
+    }
+
+    fn get_server_information(&self) -> (String, String, String, String) {
+        (
+            String::from("fake"),
+            String::from("alertify"),
+            String::from("0"),
+            String::from("1.2"),
+        )
+    }
+
+    #[zbus(signal)]
+    async fn action_invoked(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        action_key: &str,
+    ) -> zbus::Result<()>;
+
+    #[zbus(signal)]
+    async fn notification_closed(
+        emitter: &SignalEmitter<'_>,
+        id: u32,
+        reason: u32,
+    ) -> zbus::Result<()>;
+}
+
+/// A stand-in for systemd-logind that reports one session for each user ID.
+struct FakeLogind {
+    uids: Vec<u32>,
+}
+
+#[interface(name = "org.freedesktop.login1.Manager")]
+impl FakeLogind {
+    fn list_sessions(&self) -> Vec<(String, u32, String, String, OwnedObjectPath)> {
+        self.uids
+            .iter()
+            .enumerate()
+            .map(|(index, uid)| {
+                (
+                    index.to_string(),
+                    *uid,
+                    format!("user{uid}"),
+                    String::from("seat0"),
+                    OwnedObjectPath::try_from(format!("/org/freedesktop/login1/session/_{index}"))
+                        .unwrap(),
+                )
+            })
+            .collect()
+    }
+}
+
+/// Claims `org.freedesktop.login1` on the bus with sessions for `uids`.
+pub async fn start_fake_logind(address: &str, uids: Vec<u32>) -> Connection {
+    connection::Builder::address(address)
+        .unwrap()
+        .name("org.freedesktop.login1")
+        .unwrap()
+        .serve_at("/org/freedesktop/login1", FakeLogind { uids })
+        .unwrap()
+        .build()
+        .await
+        .expect("failed to start the fake logind")
+}
+
+/// The fake server, recording every call and able to act as the user.
+pub struct FakeServer {
+    connection: Connection,
+    calls: Arc<Mutex<Vec<Call>>>,
+}
+
+impl FakeServer {
+    pub async fn start(address: &str) -> FakeServer {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let server = FakeNotifications {
+            calls: Arc::clone(&calls),
+            next_id: 0,
+        };
+        let connection = connection::Builder::address(address)
+            .unwrap()
+            .name(BUS_NAME)
+            .unwrap()
+            .serve_at(OBJECT_PATH, server)
+            .unwrap()
+            .build()
+            .await
+            .expect("failed to start the fake notification server");
+        FakeServer { connection, calls }
+    }
+
+    /// Waits until at least `count` notifications arrived and returns all of them.
+    pub async fn wait_for_calls(&self, count: usize) -> Vec<Call> {
+        let deadline = Instant::now() + CALL_TIMEOUT;
+        loop {
+            if self.calls.lock().unwrap().len() >= count {
+                return std::mem::take(&mut *self.calls.lock().unwrap());
+            }
+            assert!(
+                Instant::now() < deadline,
+                "expected {count} notifications, got {}",
+                self.calls.lock().unwrap().len()
+            );
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+    }
+
+    /// Takes the notifications received so far.
+    pub fn calls(&self) -> Vec<Call> {
+        std::mem::take(&mut *self.calls.lock().unwrap())
+    }
+
+    pub async fn invoke_action(&self, id: u32, action_key: &str) {
+        let emitter = SignalEmitter::new(&self.connection, OBJECT_PATH).unwrap();
+        FakeNotifications::action_invoked(&emitter, id, action_key)
+            .await
+            .unwrap();
+    }
+
+    pub async fn close(&self, id: u32, reason: u32) {
+        let emitter = SignalEmitter::new(&self.connection, OBJECT_PATH).unwrap();
+        FakeNotifications::notification_closed(&emitter, id, reason)
+            .await
+            .unwrap();
+    }
+}
+
+/// A private bus, a fake server and scratch XDG directories for one test.
+pub struct TestEnv {
+    server: Option<FakeServer>,
+    pub bus: PrivateBus,
+    pub dir: PathBuf,
+}
+
+impl TestEnv {
+    /// A fresh environment with the fake server running, or `None` without `dbus-daemon`.
+    pub async fn start() -> Option<TestEnv> {
+        let mut env = TestEnv::without_server()?;
+        env.start_server().await;
+        Some(env)
+    }
+
+    /// An environment whose bus has no notification server until `start_server`.
+    pub fn without_server() -> Option<TestEnv> {
+        if !have_dbus() {
+            return None;
+        }
+        static COUNT: AtomicU32 = AtomicU32::new(0);
+        let dir = std::env::temp_dir().join(format!(
+            "alertify-test-{}-{}",
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ));
+        std::fs::create_dir_all(dir.join("config")).unwrap();
+        std::fs::create_dir_all(dir.join("state")).unwrap();
+
+        Some(TestEnv {
+            server: None,
+            bus: PrivateBus::start(&dir.join("bus")),
+            dir,
+        })
+    }
+
+    pub async fn start_server(&mut self) {
+        self.server = Some(FakeServer::start(&self.bus.address).await);
+    }
+
+    pub fn server(&self) -> &FakeServer {
+        self.server
+            .as_ref()
+            .expect("the fake server was not started")
+    }
+
+    pub fn config_dir(&self) -> PathBuf {
+        self.dir.join("config").join("alertify")
+    }
+
+    pub fn write_config(&self, contents: &str) {
+        std::fs::create_dir_all(self.config_dir()).unwrap();
+        std::fs::write(self.config_dir().join("config.toml"), contents).unwrap();
+    }
+
+    /// `alertify` with the given arguments, talking to this environment's bus.
+    pub fn alertify<I, S>(&self, args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        self.alertify_at(Path::new(env!("CARGO_BIN_EXE_alertify")), args)
+    }
+
+    /// Like `alertify`, but running the binary at `exe`, such as a copy.
+    pub fn alertify_at<I, S>(&self, exe: &Path, args: I) -> Command
+    where
+        I: IntoIterator<Item = S>,
+        S: AsRef<std::ffi::OsStr>,
+    {
+        let mut command = Command::new(exe);
+        command
+            .args(args)
+            .env("DBUS_SESSION_BUS_ADDRESS", &self.bus.address)
+            .env("HOME", &self.dir)
+            .env("XDG_CONFIG_HOME", self.dir.join("config"))
+            .env("XDG_STATE_HOME", self.dir.join("state"))
+            .env_remove("ALERTIFY_CONFIG")
+            .env_remove("ALERTIFY_PROFILE")
+            .env_remove("ALERTIFY_APP_NAME")
+            .env_remove("ALERTIFY_ICON")
+            .env_remove("ALERTIFY_TIMEOUT")
+            .env_remove("ALERTIFY_URGENCY")
+            .stdin(Stdio::null())
+            .kill_on_drop(true);
+        command
+    }
+
+    pub fn path(&self, name: &str) -> PathBuf {
+        self.dir.join(name)
+    }
+}
+
+impl Drop for TestEnv {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// Runs the command to completion and returns its stdout, failing on a non-zero exit.
+pub async fn run_ok(command: &mut Command) -> String {
+    let output = command.output().await.unwrap();
+    assert!(
+        output.status.success(),
+        "command failed with {}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    String::from_utf8(output.stdout).unwrap()
+}
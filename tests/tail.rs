@@ -0,0 +1,55 @@
+mod support;
+
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::time::Duration;
+use support::TestEnv;
+
+fn append(path: &Path, line: &str) {
+    let mut file = OpenOptions::new()
+        .append(true)
+        .create(true)
+        .open(path)
+        .unwrap();
+    writeln!(file, "{line}").unwrap();
+}
+
+#[tokio::test]
+async fn tail_follows_the_file_through_truncation_and_rotation() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let log = env.path("app.log");
+    append(&log, "ERROR before the start");
+    let _tail = env
+        .alertify(["tail", "--rule", "ERROR (.*)=>title:Error,body:$1"])
+        .arg(&log)
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    append(&log, "INFO ignored");
+    append(&log, "ERROR first");
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].summary, "Error");
+    assert_eq!(calls[0].body, "first");
+
+    // Truncated in place, as `copytruncate` does; shorter than before, which is how it
+    // shows.
+    std::fs::write(&log, "").unwrap();
+    tokio::time::sleep(Duration::from_millis(500)).await;
+    append(&log, "ERROR 2nd");
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].body, "2nd");
+
+    // Moved away and replaced by a new file, as `logrotate` does by default. The last line
+    // of the old file is still read from it.
+    append(&log, "ERROR last in the old file");
+    std::fs::rename(&log, env.path("app.log.1")).unwrap();
+    append(&log, "ERROR first in the new file");
+    let calls = env.server().wait_for_calls(2).await;
+    let bodies: Vec<&str> = calls.iter().map(|call| call.body.as_str()).collect();
+    assert_eq!(bodies, ["last in the old file", "first in the new file"]);
+}
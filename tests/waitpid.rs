@@ -0,0 +1,55 @@
+mod support;
+
+use std::process::Command;
+use std::time::Duration;
+use support::{TestEnv, run_ok};
+
+#[tokio::test]
+async fn wait_pid_reports_the_exit_code() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    // Left unreaped until alertify is done, since it reads the status from the zombie.
+    let mut child = Command::new("sh")
+        .args(["-c", "sleep 0.5; exit 4"])
+        .spawn()
+        .unwrap();
+    let pid = child.id().to_string();
+
+    let stdout = run_ok(&mut env.alertify(["wait-pid", &pid])).await;
+    assert!(stdout.contains("exit status 4"), "{stdout}");
+
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, format!("sh ({pid}) exited"));
+    assert!(
+        calls[0].body.starts_with("exit status 4 after"),
+        "{}",
+        calls[0].body
+    );
+    assert_eq!(calls[0].hint::<u8>("urgency"), Some(2));
+    assert_eq!(child.wait().unwrap().code(), Some(4));
+}
+
+#[tokio::test]
+async fn wait_pid_refuses_processes_that_are_gone() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let mut child = Command::new("true").spawn().unwrap();
+    let pid = child.id().to_string();
+    tokio::time::sleep(Duration::from_millis(200)).await;
+
+    let output = env.alertify(["wait-pid", &pid]).output().await.unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("has already exited"), "{stderr}");
+    child.wait().unwrap();
+
+    let output = env.alertify(["wait-pid", &pid]).output().await.unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains(&format!("no process with PID {pid}")),
+        "{stderr}"
+    );
+    assert!(env.server().calls().is_empty());
+}
@@ -0,0 +1,79 @@
+mod support;
+
+use std::process::Stdio;
+use std::time::Duration;
+use support::TestEnv;
+
+#[tokio::test]
+async fn watch_notifies_about_matching_files_with_their_icon() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let dir = env.path("inbox");
+    std::fs::create_dir_all(&dir).unwrap();
+    let _watch = env
+        .alertify(["watch", "--debounce", "100ms", "--glob", "*.pdf"])
+        .arg(&dir)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    // Give it time to set up its watches.
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    let report = dir.join("report.pdf");
+    std::fs::write(&report, "%PDF").unwrap();
+    std::fs::write(dir.join("notes.txt"), "skipped by the glob").unwrap();
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls.len(), 1);
+    assert_eq!(calls[0].summary, "report.pdf created");
+    assert_eq!(calls[0].body, report.display().to_string());
+    assert_eq!(calls[0].icon, "x-office-document");
+
+    std::fs::remove_file(&report).unwrap();
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "report.pdf deleted");
+    assert_eq!(calls[0].icon, "user-trash");
+
+    tokio::time::sleep(Duration::from_millis(300)).await;
+    assert!(env.server().calls().is_empty());
+}
+
+#[tokio::test]
+async fn watch_follows_a_file_replaced_by_renaming() {
+    let Some(env) = TestEnv::start().await else {
+        return;
+    };
+    let file = env.path("settings.conf");
+    std::fs::write(&file, "old").unwrap();
+    let watch = env
+        .alertify(["watch", "--debounce", "100ms"])
+        .arg(&file)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    tokio::time::sleep(Duration::from_secs(1)).await;
+
+    // How editors and config tools save: write a new file, then rename it over the old one.
+    for contents in ["new", "newer"] {
+        let temporary = env.path("settings.conf.tmp");
+        std::fs::write(&temporary, contents).unwrap();
+        std::fs::rename(&temporary, &file).unwrap();
+        let calls = env.server().wait_for_calls(1).await;
+        assert_eq!(calls[0].summary, "settings.conf modified", "{contents}");
+    }
+
+    std::fs::remove_file(&file).unwrap();
+    let calls = env.server().wait_for_calls(1).await;
+    assert_eq!(calls[0].summary, "settings.conf deleted");
+    let output = tokio::time::timeout(Duration::from_secs(10), watch.wait_with_output())
+        .await
+        .unwrap()
+        .unwrap();
+    assert!(!output.status.success());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        stderr.contains("every watched path was removed"),
+        "{stderr}"
+    );
+}